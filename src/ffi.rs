@@ -1,5 +1,7 @@
 use crate::log_level::LogLevel;
-use crate::logger::LogData;
+#[cfg(feature = "file")]
+use crate::logger::write_logger;
+use crate::logger::{read_logger, LogData};
 use crate::{get_logger, init_logger, LoggerConfig};
 use std::ffi::{c_uchar, c_ulonglong, CStr};
 use std::os::raw::{c_char, c_int};
@@ -38,47 +40,72 @@ pub extern "C" fn paper2_init_logger_ffi(config: *const LoggerConfigFfi, path: *
     init_logger(converted_config, path_buf).is_ok()
 }
 
+/// Contexts only exist with the `file` feature, without it the symbol is kept for the C++ header
+/// and does nothing
 #[no_mangle]
+#[allow(unused_variables)]
 pub extern "C" fn paper2_register_context_id(tag: *const c_char) {
-    if tag.is_null() {
-        return;
-    }
-
-    let Some(logger) = get_logger() else {
-        return;
-    };
-
-    let tag = unsafe { CStr::from_ptr(tag).to_string_lossy() };
-
-    let result = logger.write().unwrap().add_context(&tag);
+    #[cfg(feature = "file")]
+    {
+        if tag.is_null() {
+            return;
+        }
 
-    if let Err(report) = result {
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Info,
-            tag: None,
-            message: format!("Error creating context {tag}:\n{}", report),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        let Some(logger) = get_logger() else {
+            return;
+        };
+
+        let tag = unsafe { CStr::from_ptr(tag).to_string_lossy() };
+
+        // registering a context twice keeps its file, e.g. for contexts declared in several places
+        let result = write_logger(&logger).add_or_get_context(&tag);
+
+        if let Err(report) = result {
+            let _ = read_logger(&logger).queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: format!("Error creating context {tag}:\n{}", report),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            });
+        }
     }
 }
 
+/// Does nothing without the `file` feature, like [`paper2_register_context_id`]
 #[no_mangle]
+#[allow(unused_variables)]
 pub extern "C" fn paper2_unregister_context_id(tag: *const c_char) {
-    if tag.is_null() {
-        return;
-    }
-
-    let Some(logger) = get_logger() else {
-        return;
-    };
-
-    let tag = unsafe { CStr::from_ptr(tag).to_string_lossy() };
+    #[cfg(feature = "file")]
+    {
+        if tag.is_null() {
+            return;
+        }
 
-    logger.write().unwrap().remove_context(&tag);
+        let Some(logger) = get_logger() else {
+            return;
+        };
+
+        let tag = unsafe { CStr::from_ptr(tag).to_string_lossy() };
+
+        let result = write_logger(&logger).remove_context(&tag);
+
+        if let Err(report) = result {
+            let _ = read_logger(&logger).queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: format!("Error removing context {tag}:\n{}", report),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            });
+        }
+    }
 }
 
 #[no_mangle]
@@ -99,6 +126,11 @@ pub extern "C" fn paper2_queue_log_ffi(
         return false;
     };
 
+    // skip the string conversions entirely for filtered out levels
+    if !read_logger(&logger).is_level_enabled(level) {
+        return true;
+    }
+
     let tag = unsafe {
         tag.as_ref()
             .map(|c_str| CStr::from_ptr(c_str))
@@ -108,25 +140,17 @@ pub extern "C" fn paper2_queue_log_ffi(
     let message = unsafe { CStr::from_ptr(message).to_string_lossy().into_owned() };
     let file = unsafe { CStr::from_ptr(file).to_string_lossy().into_owned() };
 
-    let log_data = LogData {
-        level,
-        tag,
-        message,
-        file,
-        line: line as u32,
-        column: column as u32,
-        function_name: unsafe {
-            function_name
-                .as_ref()
-                .map(|c_str| CStr::from_ptr(c_str))
-                .map(|c| c.to_string_lossy().into_owned())
-        },
-        ..Default::default()
+    let function_name = unsafe {
+        function_name
+            .as_ref()
+            .map(|c_str| CStr::from_ptr(c_str))
+            .map(|c| c.to_string_lossy().into_owned())
     };
 
-    logger.read().unwrap().queue_log(log_data);
+    // stamps the log with `Utc::now()` at the call site rather than when the writer gets to it
+    let log_data = LogData::new(level, tag, message, file, line as u32, column as u32, function_name);
 
-    true
+    read_logger(&logger).queue_log(log_data).is_ok()
 }
 
 #[no_mangle]
@@ -135,27 +159,32 @@ pub extern "C" fn paper2_wait_for_flush() -> bool {
         return false;
     };
 
-    logger.read().unwrap().wait_for_flush();
+    read_logger(&logger).wait_for_flush();
 
     true
 }
 
+/// Null without the `file` feature, as there is no log directory then
 #[no_mangle]
 pub extern "C" fn paper2_get_log_directory() -> *const c_char {
-    let Some(logger) = get_logger() else {
-        return std::ptr::null();
-    };
-
-    let log_directory = logger
-        .read()
-        .unwrap()
-        .config
-        .context_log_path
-        .to_string_lossy()
-        .into_owned();
-
-    let c_str = std::ffi::CString::new(log_directory).unwrap();
-    c_str.into_raw()
+    #[cfg(not(feature = "file"))]
+    return std::ptr::null();
+
+    #[cfg(feature = "file")]
+    {
+        let Some(logger) = get_logger() else {
+            return std::ptr::null();
+        };
+
+        let log_directory = read_logger(&logger)
+            .config
+            .context_log_path
+            .to_string_lossy()
+            .into_owned();
+
+        let c_str = std::ffi::CString::new(log_directory).unwrap();
+        c_str.into_raw()
+    }
 }
 
 #[no_mangle]
@@ -164,9 +193,7 @@ pub extern "C" fn paper2_get_inited() -> bool {
         return false;
     };
 
-    let is_inited = logger
-        .read()
-        .unwrap()
+    let is_inited = read_logger(&logger)
         .is_inited()
         .load(std::sync::atomic::Ordering::SeqCst);
 
@@ -179,10 +206,7 @@ pub extern "C" fn paper2_wait_flush_timeout(timeout_ms: c_int) -> bool {
         return false;
     };
 
-    logger
-        .read()
-        .unwrap()
-        .wait_for_flush_timeout(std::time::Duration::from_millis(timeout_ms as u64));
+    read_logger(&logger).wait_for_flush_timeout(std::time::Duration::from_millis(timeout_ms as u64));
 
     true
 }
@@ -193,12 +217,14 @@ impl From<LoggerConfigFfi> for LoggerConfig {
             max_string_len: ffi.max_string_len as usize,
             log_max_buffer_count: ffi.log_max_buffer_count as usize,
             line_end: ffi.line_end as char,
+            #[cfg(feature = "file")]
             context_log_path: unsafe {
                 CStr::from_ptr(ffi.context_log_path)
                     .to_string_lossy()
                     .into_owned()
                     .into()
             },
+            ..Default::default()
         }
     }
 }
@@ -2,6 +2,7 @@ use std::{path::PathBuf, sync::OnceLock};
 
 mod log_level;
 mod logger;
+mod macros;
 mod semaphore_lite;
 
 static LOGGER: OnceLock<ThreadSafeLoggerThread> = OnceLock::new();
@@ -12,14 +13,49 @@ mod ffi;
 #[cfg(test)]
 mod tests;
 
-pub use logger::{do_log, LoggerConfig, LoggerThread, ThreadSafeLoggerThread};
+pub use log_level::LogLevel;
+pub use logger::{
+    do_log, install_panic_hook, ColorMode, CompactFormatter, DefaultFormatter, FieldValue, FormattedLog, JsonFormatter,
+    LineEnding, LogFormat, LogFormatter, LogReader, LogSink, LoggerConfig, LoggerConfigBuilder, LoggerThread,
+    LongMessagePolicy, OverflowPolicy, RotationPolicy, SinkStatus, StdStreamPolicy, TemplateFormatter,
+    ThreadSafeLoggerThread,
+};
+pub use macros::is_level_enabled;
+#[doc(hidden)]
+pub use macros::{log_with_location, LOGGING_COMPILED_IN};
+
+#[cfg(feature = "log-compat")]
+pub use logger::log_facade::{init_log_facade, PaperLogger};
+
+#[cfg(feature = "stdout")]
+pub use logger::LevelColors;
+
+#[cfg(feature = "syslog")]
+pub use logger::syslog_sink::SyslogTransport;
+
+#[cfg(feature = "tracing")]
+pub use logger::tracing_layer::PaperlogLayer;
+
+#[cfg(feature = "test-util")]
+pub use logger::TestSink;
+
+#[cfg(all(unix, feature = "signals"))]
+pub use logger::install_signal_flush;
+
+#[cfg(all(unix, feature = "signals", feature = "file"))]
+pub use logger::install_reopen_on_sighup;
 
 pub type Result<T> = color_eyre::Result<T>;
 
+/// The logger set up by [`init_logger`], which the logging macros and the FFI log to.
+/// It is the only process wide logger, any number of other [`LoggerThread`]s can run next to it
+/// and are logged to through their own handle
 pub fn get_logger() -> Option<ThreadSafeLoggerThread> {
     LOGGER.get().cloned()
 }
 
+/// Creates the global logger returned by [`get_logger`]. Only the first call creates one,
+/// later calls return it and ignore their arguments
 pub fn init_logger(config: LoggerConfig, path: PathBuf) -> Result<ThreadSafeLoggerThread> {
     let res = LOGGER
         .get_or_init(|| {
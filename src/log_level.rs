@@ -1,7 +1,9 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
+
+use color_eyre::eyre::{bail, Report};
 
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum LogLevel {
     Info,
     Warn,
@@ -9,13 +11,76 @@ pub enum LogLevel {
     Debug,
 }
 
+/// Honors width and alignment, e.g. `{:<5}` lines up all levels
 impl Display for LogLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Debug => "DEBUG",
+        })
+    }
+}
+
+/// Parses the names [`Display`] writes and the [`LogLevel::short`] forms, ignoring case
+impl FromStr for LogLevel {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = [LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error]
+            .into_iter()
+            .find(|level| s.eq_ignore_ascii_case(&level.to_string()) || s.eq_ignore_ascii_case(level.short()));
+        match level {
+            Some(level) => Ok(level),
+            None => bail!("Unknown log level {s:?}, expected one of debug, info, warn, error or d, i, w, e"),
+        }
+    }
+}
+
+impl LogLevel {
+    /// Single letter name of the level, as logcat shows it
+    pub fn short(self) -> &'static str {
+        match self {
+            LogLevel::Info => "I",
+            LogLevel::Warn => "W",
+            LogLevel::Error => "E",
+            LogLevel::Debug => "D",
+        }
+    }
+
+    /// Rank of the level from least to most severe, `Debug` being 0 and `Error` 3.
+    /// This is what levels are ordered and filtered by. The variant order is part of
+    /// the C ABI, so `as u8` gives a different number
+    pub fn as_u8(self) -> u8 {
         match self {
-            LogLevel::Info => write!(f, "INFO"),
-            LogLevel::Warn => write!(f, "WARN"),
-            LogLevel::Error => write!(f, "ERROR"),
-            LogLevel::Debug => write!(f, "DEBUG"),
+            LogLevel::Debug => 0,
+            LogLevel::Info => 1,
+            LogLevel::Warn => 2,
+            LogLevel::Error => 3,
+        }
+    }
+
+    /// Inverse of [`LogLevel::as_u8`]. Anything above 3 is `Error`, so the more severe
+    /// levels of other systems, e.g. critical or fatal, still count as errors
+    pub fn from_u8(severity: u8) -> Self {
+        match severity {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Warn,
+            _ => LogLevel::Error,
         }
     }
 }
+
+impl PartialOrd for LogLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogLevel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_u8().cmp(&other.as_u8())
+    }
+}
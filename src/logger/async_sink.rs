@@ -0,0 +1,110 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{log_level::LogLevel, Result};
+
+use super::{LogData, LogSink, SinkStatus};
+
+/// How often a log is handed to a sink which keeps answering [`SinkStatus::Retry`] before it is dropped
+const MAX_ATTEMPTS: u32 = 5;
+/// Doubled after every retry, so a log is given up on after 10 + 20 + 40 + 80ms of waiting
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// A sink running on its own worker thread, fed through a bounded channel
+/// so a slow sink can't stall the writer thread
+pub(crate) struct AsyncSink {
+    sender: Option<SyncSender<LogData>>,
+    worker: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+    min_level: LogLevel,
+}
+
+impl AsyncSink {
+    pub(crate) fn spawn<F>(sink: F, buffer: usize, min_level: LogLevel) -> Result<Self>
+    where
+        F: LogSink + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<LogData>(buffer);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let worker_dropped = dropped.clone();
+
+        let worker = thread::Builder::new()
+            .name("paper2-async-sink".to_string())
+            .spawn(move || {
+                for log in receiver {
+                    if !deliver(&sink, &log) {
+                        worker_dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            sender: Some(sender),
+            worker: Some(worker),
+            dropped,
+            min_level,
+        })
+    }
+
+    /// Hands the log to the worker, dropping it if the buffer is full.
+    /// Logs below `min_level` are skipped without taking up buffer space
+    pub(crate) fn send(&self, log: &LogData) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if log.level < self.min_level {
+            return;
+        }
+
+        match sender.try_send(log.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub(crate) fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for AsyncSink {
+    fn drop(&mut self) {
+        // closing the channel lets the worker finish the buffered logs and exit
+        self.sender.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Hands the log to the sink, backing off while it asks for a retry.
+/// Returns whether the sink accepted it in the end.
+/// The channel fills up while the worker waits here, so the writer thread starts
+/// dropping logs for this sink instead of being slowed down itself
+fn deliver<F: LogSink>(sink: &F, log: &LogData) -> bool {
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        match sink.send(log) {
+            SinkStatus::Accepted => return true,
+            SinkStatus::Dropped => return false,
+            SinkStatus::Retry if attempt < MAX_ATTEMPTS => {
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            SinkStatus::Retry => {}
+        }
+    }
+
+    false
+}
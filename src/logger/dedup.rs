@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use super::LogData;
+
+/// How long a run of repeated logs may stay pending before its summary is written
+pub(crate) const DEDUP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Collapses consecutive identical logs into a single "last message repeated N times" line
+#[derive(Debug, Default)]
+pub(crate) struct Deduplicator {
+    last: Option<LogData>,
+    repeats: usize,
+    last_seen: Option<Instant>,
+}
+
+impl Deduplicator {
+    /// Pushes the logs to write for `log` onto `out`, nothing if it repeats the previous log.
+    /// A pending summary always goes out before the next distinct log to keep the order
+    pub(crate) fn push(&mut self, log: LogData, out: &mut Vec<LogData>) {
+        self.last_seen = Some(Instant::now());
+
+        if self.last.as_ref().is_some_and(|last| is_repeat(last, &log)) {
+            self.repeats += 1;
+            return;
+        }
+
+        out.extend(self.take_summary());
+        self.last = Some(log.clone());
+        out.push(log);
+    }
+
+    /// Pushes `log` onto `out` without ever collapsing it, after the pending summary if any
+    pub(crate) fn pass_through(&mut self, log: LogData, out: &mut Vec<LogData>) {
+        out.extend(self.take_summary());
+        self.last = None;
+        out.push(log);
+    }
+
+    pub(crate) fn has_pending(&self) -> bool {
+        self.repeats > 0
+    }
+
+    pub(crate) fn is_expired(&self) -> bool {
+        self.has_pending()
+            && self
+                .last_seen
+                .is_some_and(|last_seen| last_seen.elapsed() >= DEDUP_TIMEOUT)
+    }
+
+    /// The summary of the current run, if any repeats were collapsed.
+    /// Further repeats of the same log start a new run
+    pub(crate) fn take_summary(&mut self) -> Option<LogData> {
+        if self.repeats == 0 {
+            return None;
+        }
+
+        let last = self.last.as_ref()?;
+        let summary = LogData {
+            message: format!("last message repeated {} times", self.repeats),
+            timestamp: Utc::now(),
+            ..last.clone()
+        };
+        self.repeats = 0;
+
+        Some(summary)
+    }
+}
+
+/// Timestamps are ignored, the rest of what identifies a log line is compared
+fn is_repeat(last: &LogData, log: &LogData) -> bool {
+    last.level == log.level
+        && last.tag == log.tag
+        && last.file == log.file
+        && last.line == log.line
+        && last.message == log.message
+        && last.fields == log.fields
+}
@@ -0,0 +1,102 @@
+use std::{ffi::OsStr, iter, os::windows::ffi::OsStrExt, ptr};
+
+use color_eyre::eyre::bail;
+use windows_sys::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::HANDLE,
+        System::EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+            EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+        },
+    },
+};
+
+use super::LogData;
+use crate::{log_level::LogLevel, Result};
+
+/// Reports logs to the Windows Event Log under an event source.
+/// No message file is registered, so Event Viewer shows the log text as the event's insertion string
+pub(crate) struct EventLogSink {
+    source: String,
+    handle: HANDLE,
+}
+
+// event log handles can be used from any thread
+unsafe impl Send for EventLogSink {}
+unsafe impl Sync for EventLogSink {}
+
+impl EventLogSink {
+    pub(crate) fn new(source: &str) -> Result<Self> {
+        let wide_source = to_wide(source);
+        // SAFETY: `wide_source` is nul terminated and outlives the call
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), wide_source.as_ptr()) };
+        if handle.is_null() {
+            bail!(
+                "Unable to register event source {source}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            handle,
+        })
+    }
+
+    pub(crate) fn send(&self, log: &LogData) -> Result<()> {
+        let message = match &log.tag {
+            Some(tag) => to_wide(&format!("[{tag}] {}", log.message)),
+            None => to_wide(&log.message),
+        };
+        let strings: [PCWSTR; 1] = [message.as_ptr()];
+
+        // SAFETY: `handle` stays registered until drop and `strings` points to one nul terminated string
+        let reported = unsafe {
+            ReportEventW(
+                self.handle,
+                event_type(log.level),
+                0,
+                0,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null(),
+            )
+        };
+        if reported == 0 {
+            bail!(
+                "Unable to report event to {}: {}",
+                self.source,
+                std::io::Error::last_os_error()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for EventLogSink {
+    fn drop(&mut self) {
+        // SAFETY: `handle` was returned by `RegisterEventSourceW` and isn't used after this
+        unsafe { DeregisterEventSource(self.handle) };
+    }
+}
+
+fn event_type(level: LogLevel) -> REPORT_EVENT_TYPE {
+    match level {
+        LogLevel::Error => EVENTLOG_ERROR_TYPE,
+        LogLevel::Warn => EVENTLOG_WARNING_TYPE,
+        LogLevel::Info | LogLevel::Debug => EVENTLOG_INFORMATION_TYPE,
+    }
+}
+
+/// `value` as a nul terminated UTF-16 string, interior nuls would cut the event text short
+fn to_wide(value: &str) -> Vec<u16> {
+    OsStr::new(value)
+        .encode_wide()
+        .map(|c| if c == 0 { u16::from(b' ') } else { c })
+        .chain(iter::once(0))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use std::{fmt, time::Duration};
+
+const BYTE_UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// A typed value for [`LogData::fields`](super::LogData::fields), rendered consistently
+/// across log calls. Converts into the `String` the fields are stored as, so it can be
+/// passed straight to [`LoggerThread::queue_log_with_fields`](super::LoggerThread::queue_log_with_fields)
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    /// Rendered in binary units, `512 B` or `1.5 MiB`
+    Bytes(u64),
+    /// Rendered in the largest unit below a second, `340ms` or `12µs`, or as `1.5s`
+    Duration(Duration),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Str(s) => f.write_str(s),
+            FieldValue::Int(i) => write!(f, "{i}"),
+            FieldValue::Bytes(bytes) => {
+                if *bytes < 1024 {
+                    return write!(f, "{bytes} B");
+                }
+
+                let mut value = *bytes as f64 / 1024.0;
+                let mut unit = 0;
+                while value >= 1024.0 && unit < BYTE_UNITS.len() - 1 {
+                    value /= 1024.0;
+                    unit += 1;
+                }
+                write!(f, "{value:.1} {}", BYTE_UNITS[unit])
+            }
+            FieldValue::Duration(duration) => {
+                let nanos = duration.as_nanos();
+                match nanos {
+                    _ if nanos < 1_000 => write!(f, "{nanos}ns"),
+                    _ if nanos < 1_000_000 => write!(f, "{}µs", nanos / 1_000),
+                    _ if nanos < 1_000_000_000 => write!(f, "{}ms", nanos / 1_000_000),
+                    _ => write!(f, "{:.1}s", duration.as_secs_f64()),
+                }
+            }
+        }
+    }
+}
+
+impl From<FieldValue> for String {
+    fn from(value: FieldValue) -> Self {
+        match value {
+            FieldValue::Str(s) => s,
+            value => value.to_string(),
+        }
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_string())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Int(value)
+    }
+}
+
+impl From<Duration> for FieldValue {
+    fn from(value: Duration) -> Self {
+        FieldValue::Duration(value)
+    }
+}
@@ -1,28 +1,566 @@
 use std::{
-    io::Write,
-    sync::{Arc, RwLock},
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    thread::JoinHandle,
 };
 
-use super::LoggerThread;
+use chrono::{DateTime, Utc};
 
-pub(crate) fn do_log(
-    log: &super::LogData,
-    logger_thread_lock: Arc<RwLock<LoggerThread>>,
-) -> std::io::Result<()> {
-    let log_line = format!("{}\n", log.format());
+use super::{read_logger, LogData, LoggerConfig, LoggerThread, RotationPolicy};
+use crate::log_level::LogLevel;
 
-    let mut logger_thread = logger_thread_lock.write().unwrap();
+/// A buffered log file which keeps track of how many bytes were written to it and when
+/// it was opened, so it can be rolled over according to `LoggerConfig::rotation`
+pub(crate) struct LogFile {
+    // `name.log` even when gzipped, rotated names are derived from it
+    path: PathBuf,
+    gzip: bool,
+    writer: BufWriter<FileWriter>,
+    written: u64,
+    // when the first log in this file was written, as far as we know
+    opened: DateTime<Utc>,
+    // compression of the last rotated file, running off the write path
+    compression: Option<JoinHandle<std::io::Result<()>>>,
+}
+
+impl LogFile {
+    /// Opens the file at `path`, truncating it unless `append` is set. An appended file
+    /// counts its existing length towards a size rotation, and its last modification
+    /// as the time it was opened for a time rotation.
+    /// Writes are buffered up to `buffer_capacity` bytes.
+    ///
+    /// With `gzip` set and the `compression` feature, `name.log.gz` is written instead, compressed
+    /// as it goes. An appended file gets another gzip member, which decoders read as one stream
+    pub(crate) fn create(
+        path: impl Into<PathBuf>,
+        append: bool,
+        buffer_capacity: usize,
+        gzip: bool,
+    ) -> std::io::Result<Self> {
+        let path = path.into();
+        let gzip = gzip && cfg!(feature = "compression");
+        let file_path = match gzip {
+            true => compressed_path(&path),
+            false => path.clone(),
+        };
+        let file = match append {
+            true => OpenOptions::new().append(true).create(true).open(&file_path)?,
+            false => File::create(&file_path)?,
+        };
+        let metadata = file.metadata()?;
+        let written = metadata.len();
+        let opened = match written {
+            0 => Utc::now(),
+            _ => metadata.modified().map(DateTime::from).unwrap_or_else(|_| Utc::now()),
+        };
+
+        Ok(Self {
+            path,
+            gzip,
+            writer: BufWriter::with_capacity(buffer_capacity, FileWriter::new(file, gzip)),
+            written,
+            opened,
+            compression: None,
+        })
+    }
+
+    /// [`LogFile::create`] with the `append`, `write_buffer_capacity` and `compress_active` of `config`
+    pub(crate) fn open(path: impl Into<PathBuf>, config: &LoggerConfig) -> std::io::Result<Self> {
+        Self::create(
+            path,
+            config.append,
+            config.write_buffer_capacity,
+            config.compress_active,
+        )
+    }
+
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Where the file is on disk, `name.log.gz` when it is gzipped
+    pub(crate) fn path(&self) -> PathBuf {
+        match self.gzip {
+            true => compressed_path(&self.path),
+            false => self.path.clone(),
+        }
+    }
+
+    pub(crate) fn opened(&self) -> DateTime<Utc> {
+        self.opened
+    }
+
+    ///
+    /// Moves `name.log` to `name.1.log`, `name.1.log` to `name.2.log` and so on,
+    /// deleting anything past `max_files`, then reopens a fresh `name.log`.
+    /// The caller holds the lock over this file, so no write can land in between.
+    ///
+    /// With `compress` set, `name.1.log` is gzipped to `name.1.log.gz` on a separate thread.
+    /// A failed compression of the previous rotation is returned rather than failing the
+    /// rotation, it shouldn't stop logging.
+    pub(crate) fn rotate(&mut self, max_files: usize, compress: bool) -> std::io::Result<Option<std::io::Error>> {
+        self.finish_writer()?;
+
+        // the previous compression may still read `name.1.log`, let it finish before shifting
+        let compression_error = self.join_compression();
+
+        if max_files > 0 {
+            let oldest = rotated_path(&self.path, max_files);
+            for oldest in [compressed_path(&oldest), oldest] {
+                if oldest.exists() {
+                    fs::remove_file(&oldest)?;
+                }
+            }
+
+            for i in (1..max_files).rev() {
+                let from = rotated_path(&self.path, i);
+                let to = rotated_path(&self.path, i + 1);
+
+                for (from, to) in [(compressed_path(&from), compressed_path(&to)), (from, to)] {
+                    if from.exists() {
+                        fs::rename(&from, to)?;
+                    }
+                }
+            }
+
+            let rotated = rotated_path(&self.path, 1);
+            match self.gzip {
+                // compressed as it was written already
+                true => fs::rename(self.path(), compressed_path(&rotated))?,
+                false => {
+                    fs::rename(&self.path, &rotated)?;
+
+                    if compress {
+                        self.compression = spawn_compression(rotated);
+                    }
+                }
+            }
+        }
+
+        self.reopen()?;
+
+        Ok(compression_error)
+    }
+
+    ///
+    /// Moves `name.log` to `name-{period}.log` and reopens a fresh `name.log`, deleting
+    /// the oldest timestamped files past `max_files`. Compression works like in [`LogFile::rotate`]
+    pub(crate) fn rotate_to_period(
+        &mut self,
+        period: &str,
+        max_files: usize,
+        compress: bool,
+    ) -> std::io::Result<Option<std::io::Error>> {
+        self.finish_writer()?;
+
+        let compression_error = self.join_compression();
+
+        let rotated = period_path(&self.path, period);
+        match self.gzip {
+            true => fs::rename(self.path(), compressed_path(&rotated))?,
+            false => fs::rename(&self.path, &rotated)?,
+        }
+
+        // timestamps sort by name, so the oldest files come first
+        let mut periods = period_files(&self.path, period)?;
+        periods.sort();
+        let expired = periods.len().saturating_sub(max_files);
+        for path in &periods[..expired] {
+            fs::remove_file(path)?;
+        }
+
+        // a gzipped file was moved to `rotated.gz` above
+        if compress && rotated.exists() {
+            self.compression = spawn_compression(rotated);
+        }
+
+        self.reopen()?;
+
+        Ok(compression_error)
+    }
+
+    fn reopen(&mut self) -> std::io::Result<()> {
+        let file = File::create(self.path())?;
+        self.writer = BufWriter::with_capacity(self.writer.capacity(), FileWriter::new(file, self.gzip));
+        self.written = 0;
+        self.opened = Utc::now();
 
-    let global_file = &mut logger_thread.global_file;
-    global_file.write_all(log_line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Flushes and reopens the file at its path, appending to whatever is there now.
+    /// Once an external tool like `logrotate` renamed the file, writes go to a fresh file
+    /// at the path instead of following the renamed one
+    pub(crate) fn reopen_at_path(&mut self) -> std::io::Result<()> {
+        self.finish_writer()?;
+
+        let reopened = Self::create(&self.path, true, self.writer.capacity(), self.gzip)?;
+        self.writer = reopened.writer;
+        self.written = reopened.written;
+        self.opened = reopened.opened;
+
+        Ok(())
+    }
+
+    /// Flushes the buffered tail and waits for a running compression, so neither of their
+    /// errors is lost to `BufWriter`'s silent flush on drop
+    pub(crate) fn close(mut self) -> std::io::Result<()> {
+        self.finish_writer()?;
+
+        match self.join_compression() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
 
-    if let Some(context_file) = log
-        .tag
-        .as_ref()
-        .and_then(|tag| logger_thread.context_map.get_mut(tag))
-    {
-        context_file.write_all(log_line.as_bytes())?;
+    /// Flushes the buffered tail and completes a gzipped file, which takes no further writes
+    fn finish_writer(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_mut().finish()
+    }
+
+    /// Waits for a running compression, returning its error if it failed
+    fn join_compression(&mut self) -> Option<std::io::Error> {
+        match self.compression.take()?.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e),
+            Err(_) => Some(std::io::Error::other("Compression thread panicked")),
+        }
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // only what was actually accepted counts, a short write is retried by `write_all`
+        let written = self.writer.write(buf)?;
+        self.written = self.written.saturating_add(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// The file under a [`LogFile`]'s buffer
+enum FileWriter {
+    Plain(File),
+    /// Flushing emits a sync point, so everything flushed can be decompressed
+    /// even if the process dies before the gzip trailer is written
+    #[cfg(feature = "compression")]
+    Gzip(flate2::write::GzEncoder<File>),
+}
+
+impl FileWriter {
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    fn new(file: File, gzip: bool) -> Self {
+        #[cfg(feature = "compression")]
+        if gzip {
+            return Self::Gzip(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        }
+
+        Self::Plain(file)
+    }
+
+    /// Writes the gzip trailer. Dropping the encoder would do so as well, but without reporting errors
+    fn finish(&mut self) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(_) => Ok(()),
+            #[cfg(feature = "compression")]
+            FileWriter::Gzip(encoder) => encoder.try_finish(),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            FileWriter::Plain(file) => file.write(buf),
+            #[cfg(feature = "compression")]
+            FileWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            FileWriter::Plain(file) => file.flush(),
+            #[cfg(feature = "compression")]
+            FileWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Where the lines of the global file go
+pub(crate) enum GlobalOutput {
+    File(LogFile),
+    /// Handed to [`LoggerThread::with_writer`]. It has no path, so it is never rotated
+    Writer(BufWriter<Box<dyn Write + Send>>),
+}
+
+impl Write for GlobalOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            GlobalOutput::File(file) => file.write(buf),
+            GlobalOutput::Writer(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            GlobalOutput::File(file) => file.flush(),
+            GlobalOutput::Writer(writer) => writer.flush(),
+        }
+    }
+}
+
+/// `global.log` -> `global.{index}.log`
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}.{index}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{index}"),
+    };
+
+    path.with_file_name(file_name)
+}
+
+/// `global.log` -> `global-{period}.log`
+fn period_path(path: &Path, period: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}-{period}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{period}"),
+    };
+
+    path.with_file_name(file_name)
+}
+
+/// Files next to `global.log` looking like `global-2024-06-01.log` or `global-2024-06-01.log.gz`.
+/// Their period has to be shaped exactly like `period`, so the files of a sibling context
+/// like `global-1.log` aren't mistaken for old periods
+fn period_files(path: &Path, period: &str) -> std::io::Result<Vec<PathBuf>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let dir = match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => parent,
+        None => Path::new("."),
+    };
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        let file_period = file_name
+            .strip_prefix(&*stem)
+            .and_then(|name| name.strip_prefix('-'))
+            .map(|name| name.strip_suffix(".gz").unwrap_or(name))
+            .and_then(|name| name.strip_suffix(&extension));
+        if file_period.is_some_and(|file_period| is_same_period_shape(file_period, period)) {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether `candidate` has digits exactly where `period` has them and the same separators
+/// elsewhere, e.g. `2024-05-31` for a daily `2024-06-01`
+fn is_same_period_shape(candidate: &str, period: &str) -> bool {
+    candidate.len() == period.len()
+        && candidate
+            .chars()
+            .zip(period.chars())
+            .all(|(c, p)| match p.is_ascii_digit() {
+                true => c.is_ascii_digit(),
+                false => c == p,
+            })
+}
+
+/// `global.1.log` -> `global.1.log.gz`
+fn compressed_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".gz");
+
+    path.with_file_name(file_name)
+}
+
+#[cfg(feature = "compression")]
+fn spawn_compression(path: PathBuf) -> Option<JoinHandle<std::io::Result<()>>> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let handle = std::thread::spawn(move || {
+        let mut source = File::open(&path)?;
+        let target = BufWriter::new(File::create(compressed_path(&path))?);
+        let mut encoder = GzEncoder::new(target, Compression::default());
+
+        std::io::copy(&mut source, &mut encoder)?;
+        encoder.finish()?.flush()?;
+        drop(source);
+
+        fs::remove_file(&path)
+    });
+
+    Some(handle)
+}
+
+/// Rotated files are left uncompressed without the `compression` feature
+#[cfg(not(feature = "compression"))]
+fn spawn_compression(_path: PathBuf) -> Option<JoinHandle<std::io::Result<()>>> {
+    None
+}
+
+/// Every line is written to the global file, so it stays a complete log of the process.
+/// Lines whose tag matches a context registered with [`LoggerThread::add_context`]
+/// are additionally written to that context's file, formatted again if the context has its own
+/// formatter. Untagged lines and tags without a context only end up in the global file.
+///
+/// The lines are formatted into one buffer per file first, so each file sees a single
+/// write per drain of the queue. Order within each file is preserved.
+/// Rotation is checked once per batch. A file may exceed a size limit by one batch,
+/// a time rotation happens before the first batch of the new period is written
+pub(crate) fn do_log(logs: &[LogData], logger_thread_lock: Arc<RwLock<LoggerThread>>) -> std::io::Result<()> {
+    let logger_thread = read_logger(&logger_thread_lock);
+    let config = &logger_thread.config;
+    let formatter = &logger_thread.formatter;
+
+    let mut global_output = logger_thread
+        .global_output
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let Some(global_output) = global_output.as_mut() else {
+        return write_fallback_logs(logs, &logger_thread);
+    };
+
+    let mut context_map = logger_thread.context_map.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut error_file = logger_thread.error_file.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let mut global_lines = Vec::new();
+    let mut context_lines: HashMap<&str, Vec<u8>> = HashMap::new();
+    let mut error_lines = Vec::new();
+    for log in logs {
+        let start = global_lines.len();
+        formatter.format(log, &mut global_lines)?;
+        if global_lines.len() == start {
+            continue;
+        }
+        config.line_ending.terminate(&mut global_lines);
+
+        if error_file.is_some() && log.level >= config.error_log_level {
+            error_lines.extend_from_slice(&global_lines[start..]);
+        }
+
+        let Some(tag) = log.tag.as_deref().filter(|tag| context_map.contains_key(*tag)) else {
+            continue;
+        };
+        let lines = context_lines.entry(tag).or_default();
+        match logger_thread.context_formatters.get(tag) {
+            Some(context_formatter) => {
+                let context_start = lines.len();
+                context_formatter.format(log, lines)?;
+                if lines.len() > context_start {
+                    config.line_ending.terminate(lines);
+                }
+            }
+            None => lines.extend_from_slice(&global_lines[start..]),
+        }
+    }
+
+    let now_period = config.rotation.period(Utc::now(), config.use_local_time);
+
+    let write_lines = |file: &mut LogFile, lines: &[u8], tag: Option<&str>| -> std::io::Result<()> {
+        let mut compression_error = None;
+
+        if let Some(period) = config.rotation.period(file.opened(), config.use_local_time) {
+            if now_period.as_ref() != Some(&period) {
+                compression_error = file.rotate_to_period(&period, config.max_files, config.compress_rotated)?;
+            }
+        }
+
+        file.write_all(lines)?;
+
+        if let RotationPolicy::Size(max_file_size) = config.rotation {
+            if file.written() > max_file_size {
+                compression_error = file.rotate(config.max_files, config.compress_rotated)?;
+            }
+        }
+
+        // report into the fresh file instead of failing, the logs themselves are intact
+        if let Some(e) = compression_error {
+            let error_log = LogData {
+                level: LogLevel::Error,
+                tag: tag.map(|tag| tag.to_string().into()),
+                message: format!("Unable to compress rotated log file: {e}"),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                ..Default::default()
+            };
+            let formatter = tag
+                .and_then(|tag| logger_thread.context_formatters.get(tag))
+                .unwrap_or(formatter);
+            let mut error_line = Vec::new();
+            formatter.format(&error_log, &mut error_line)?;
+            config.line_ending.terminate(&mut error_line);
+            file.write_all(&error_line)?;
+        }
+
+        Ok(())
+    };
+
+    if !global_lines.is_empty() {
+        match global_output {
+            GlobalOutput::File(global_file) => write_lines(global_file, &global_lines, None)?,
+            GlobalOutput::Writer(writer) => writer.write_all(&global_lines)?,
+        }
+    }
+
+    for (tag, lines) in context_lines {
+        if let Some(context_file) = context_map.get_mut(tag) {
+            write_lines(context_file, &lines, Some(tag))?;
+        }
+    }
+
+    if let Some(error_file) = error_file.as_mut().filter(|_| !error_lines.is_empty()) {
+        write_lines(error_file, &error_lines, None)?;
     }
 
     Ok(())
 }
+
+/// Stands in for the global file once file logging fell back to stdout
+fn write_fallback_logs(logs: &[LogData], logger_thread: &LoggerThread) -> std::io::Result<()> {
+    // the stdout logger prints these already
+    if cfg!(feature = "stdout") {
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    for log in logs {
+        let start = lines.len();
+        logger_thread.formatter.format(log, &mut lines)?;
+        if lines.len() > start {
+            logger_thread.config.line_ending.terminate(&mut lines);
+        }
+    }
+
+    write_fallback(&lines)
+}
+
+/// Writes already formatted lines meant for the global file to stdout, unless the
+/// stdout logger prints them already
+pub(crate) fn write_fallback(lines: &[u8]) -> std::io::Result<()> {
+    match cfg!(feature = "stdout") {
+        true => Ok(()),
+        false => std::io::stdout().lock().write_all(lines),
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, Write};
+
+use super::{
+    log_data::{TextLayout, DEFAULT_TAG, DEFAULT_TIMESTAMP_FORMAT},
+    LogData,
+};
+#[cfg(feature = "stdout")]
+use crate::log_level::LogLevel;
+
+/// Renders a single log line for the file and stdout loggers.
+/// Set a custom one with [`LoggerThread::set_formatter`](super::LoggerThread::set_formatter)
+pub trait LogFormatter: Send + Sync {
+    fn format(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Used by the stdout logger when colors are enabled, plain [`LogFormatter::format`] by default
+    fn format_colored(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        self.format(data, out)
+    }
+}
+
+/// Length of the longest level name
+const LEVEL_WIDTH: usize = 5;
+/// Default of [`DefaultFormatter::tag_width`]
+pub(crate) const DEFAULT_TAG_WIDTH: usize = 12;
+
+/// The layout of [`LogData::format`]
+#[derive(Debug, Clone)]
+pub struct DefaultFormatter {
+    pub timestamp_format: String,
+    pub use_local_time: bool,
+    /// Tag of untagged logs, the `[tag]` segment is left out for them if `None`
+    pub default_tag: Option<String>,
+    /// Writes the `file:line:column@function` block, leaving `level [time] [tag] message` if `false`
+    pub show_source_location: bool,
+    /// Writes the `#seq` of each log after the timestamp
+    pub show_seq: bool,
+    /// Pads the level and the `[tag]` so the columns after them line up
+    pub align_columns: bool,
+    /// Chars the tag is padded to with `align_columns`, longer tags push the line out
+    pub tag_width: usize,
+    /// Written in front of every line, e.g. `host=web-1 pid=4242` from `LoggerConfig::include_pid`
+    /// and `LoggerConfig::include_hostname`
+    pub origin: Option<String>,
+    /// Styles of the level in [`LogFormatter::format_colored`]
+    #[cfg(feature = "stdout")]
+    pub level_colors: LevelColors,
+}
+
+impl Default for DefaultFormatter {
+    fn default() -> Self {
+        Self {
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            use_local_time: false,
+            default_tag: Some(DEFAULT_TAG.to_string()),
+            show_source_location: true,
+            show_seq: false,
+            align_columns: false,
+            tag_width: DEFAULT_TAG_WIDTH,
+            origin: None,
+            #[cfg(feature = "stdout")]
+            level_colors: LevelColors::default(),
+        }
+    }
+}
+
+impl DefaultFormatter {
+    fn layout(&self) -> TextLayout<'_> {
+        TextLayout {
+            timestamp_format: &self.timestamp_format,
+            use_local_time: self.use_local_time,
+            default_tag: self.default_tag.as_deref(),
+            show_source_location: self.show_source_location,
+            show_seq: self.show_seq,
+            tag_width: match self.align_columns {
+                true => self.tag_width,
+                false => 0,
+            },
+        }
+    }
+
+    /// Width the level is padded to, 0 for none
+    fn level_width(&self) -> usize {
+        match self.align_columns {
+            true => LEVEL_WIDTH,
+            false => 0,
+        }
+    }
+}
+
+impl LogFormatter for DefaultFormatter {
+    fn format(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(origin) = &self.origin {
+            write!(out, "{origin} ")?;
+        }
+        write!(out, "{:<1$} ", data.level, self.level_width())?;
+        data.write_text_body(out, &self.layout())
+    }
+
+    #[cfg(feature = "stdout")]
+    fn format_colored(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        use owo_colors::OwoColorize;
+
+        if let Some(origin) = &self.origin {
+            write!(out, "{origin} ")?;
+        }
+        // only the level is colored so grepping the output stays unaffected
+        write!(
+            out,
+            "{:<1$} ",
+            data.level.style(self.level_colors.style(data.level)),
+            self.level_width()
+        )?;
+
+        data.write_text_body(out, &self.layout())
+    }
+}
+
+/// Terminal style of each level's name in colored output
+#[cfg(feature = "stdout")]
+#[derive(Debug, Clone, Copy)]
+pub struct LevelColors {
+    pub error: owo_colors::Style,
+    pub warn: owo_colors::Style,
+    pub info: owo_colors::Style,
+    pub debug: owo_colors::Style,
+}
+
+#[cfg(feature = "stdout")]
+impl LevelColors {
+    pub fn style(&self, level: LogLevel) -> owo_colors::Style {
+        match level {
+            LogLevel::Error => self.error,
+            LogLevel::Warn => self.warn,
+            LogLevel::Info => self.info,
+            LogLevel::Debug => self.debug,
+        }
+    }
+}
+
+/// Red errors, yellow warnings, green info and dimmed gray debug logs
+#[cfg(feature = "stdout")]
+impl Default for LevelColors {
+    fn default() -> Self {
+        use owo_colors::Style;
+
+        Self {
+            error: Style::new().red(),
+            warn: Style::new().yellow(),
+            info: Style::new().green(),
+            debug: Style::new().bright_black().dimmed(),
+        }
+    }
+}
+
+/// The layout of [`LogData::write_compact_to_io`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactFormatter;
+
+impl LogFormatter for CompactFormatter {
+    fn format(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        data.write_compact_to_io(out)
+    }
+}
+
+/// The layout of [`LogData::write_json_to_io`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl LogFormatter for JsonFormatter {
+    fn format(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        data.write_json_to_io(out)
+    }
+}
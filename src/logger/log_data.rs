@@ -1,57 +1,277 @@
-use std::time::Instant;
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    thread::ThreadId,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, Utc,
+};
 
 use crate::log_level::LogLevel;
 
 pub const DEFAULT_TAG: &str = "GLOBAL";
+/// Milliseconds keep logs from a hot loop ordered within the same second
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Whether chrono can format timestamps with this strftime string
+pub fn is_valid_timestamp_format(timestamp_format: &str) -> bool {
+    !StrftimeItems::new(timestamp_format).any(|item| matches!(item, Item::Error))
+}
+
+/// Settings of the text layout written by [`LogData::write_text_body`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TextLayout<'a> {
+    pub timestamp_format: &'a str,
+    pub use_local_time: bool,
+    /// Tag of untagged logs, the `[tag]` segment is left out for them if `None`
+    pub default_tag: Option<&'a str>,
+    pub show_source_location: bool,
+    pub show_seq: bool,
+    /// Tags are padded to this many chars so the columns after them line up, 0 to not pad them
+    pub tag_width: usize,
+}
 
 #[derive(Debug, Clone)]
 pub struct LogData {
     pub level: LogLevel,
-    pub tag: Option<String>,
+    /// Usually a literal, borrowed so logging from hot loops doesn't allocate for it
+    pub tag: Option<Cow<'static, str>>,
     pub message: String,
-    pub timestamp: Instant,
+    pub timestamp: DateTime<Utc>,
 
-    pub file: String,
+    /// Usually `file!()`, borrowed so logging from hot loops doesn't allocate for it
+    pub file: Cow<'static, str>,
     pub line: u32,
     pub column: u32,
     pub function_name: Option<String>,
+
+    /// The thread which queued the log, filled in by [`LoggerThread::queue_log`](super::LoggerThread::queue_log)
+    /// since the write itself happens later on the logger thread
+    pub thread_id: Option<ThreadId>,
+    pub thread_name: Option<String>,
+
+    /// Structured context, written as `key=value` pairs after the message
+    pub fields: Vec<(String, String)>,
+
+    /// Position of this chunk within a message split over several lines, see [`LogData::chunk_total`]
+    pub chunk_index: usize,
+    /// How many chunks the original message was split into, `1` if it wasn't split.
+    /// Split messages get a `[index/total]` marker in front of each chunk
+    pub chunk_total: usize,
+
+    /// Position in the order logs were queued in, counting from 1, for ordering logs with
+    /// equal timestamps. Assigned by [`LoggerThread::queue_log`](super::LoggerThread::queue_log),
+    /// `0` until then. Gaps mean logs were dropped on the way
+    pub seq: u64,
+
+    /// When [`LoggerThread::queue_log`](super::LoggerThread::queue_log) queued the log and when
+    /// the writer thread wrote it, only filled in with `LoggerConfig::track_write_latency`.
+    /// `LoggerConfig::max_queue_age` fills in `enqueued_at` on its own.
+    /// Text logs show the difference as `+1.234ms` after the timestamp
+    pub enqueued_at: Option<DateTime<Utc>>,
+    pub written_at: Option<DateTime<Utc>>,
 }
 
 impl LogData {
+    /// `tag` and `file` take either owned strings or `&'static str` literals, the latter without allocating
     pub fn new(
         level: LogLevel,
-        tag: Option<String>,
+        tag: Option<impl Into<Cow<'static, str>>>,
         message: String,
-        file: String,
+        file: impl Into<Cow<'static, str>>,
         line: u32,
         column: u32,
         function_name: Option<String>,
     ) -> Self {
         Self {
             level,
-            tag,
+            tag: tag.map(Into::into),
             message,
-            timestamp: Instant::now(),
-            file,
+            timestamp: Utc::now(),
+            file: file.into(),
             line,
             column,
             function_name,
+            thread_id: None,
+            thread_name: None,
+            fields: Vec::new(),
+            chunk_index: 0,
+            chunk_total: 1,
+            seq: 0,
+            enqueued_at: None,
+            written_at: None,
         }
     }
 
     pub fn format(&self) -> String {
+        self.format_with(DEFAULT_TIMESTAMP_FORMAT, false)
+    }
+
+    /// [`LogData::format`] with a custom strftime timestamp format, optionally in local time
+    pub fn format_with(&self, timestamp_format: &str, use_local_time: bool) -> String {
         format!(
-            "{} [{:?}] [{}] {file}:{line}:{column}@{function_name} {}\n",
+            "{} [{}] {}{}{file}:{line}:{column}@{function_name} {}{}{}\n",
             self.level,
-            self.timestamp,
-            self.tag.as_deref().unwrap_or(DEFAULT_TAG),
+            self.format_timestamp(timestamp_format, use_local_time),
+            TagSegment(self.tag.as_deref().or(Some(DEFAULT_TAG))),
+            ThreadMarker(self),
+            ChunkMarker(self),
             self.message,
+            TextFields(&self.fields),
             line = self.line,
             column = self.column,
             file = self.file,
             function_name = self.function_name.as_deref().unwrap_or("default")
         )
     }
+
+    /// Writes the same layout as [`LogData::format`] without allocating the line first
+    pub fn write_to_io(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.write_to_io_with(writer, DEFAULT_TIMESTAMP_FORMAT, false)
+    }
+
+    /// Writes the same layout as [`LogData::format_with`] without allocating the line first
+    pub fn write_to_io_with(
+        &self,
+        writer: &mut dyn Write,
+        timestamp_format: &str,
+        use_local_time: bool,
+    ) -> io::Result<()> {
+        write!(writer, "{} ", self.level)?;
+        self.write_text_body(
+            writer,
+            &TextLayout {
+                timestamp_format,
+                use_local_time,
+                default_tag: Some(DEFAULT_TAG),
+                show_source_location: true,
+                show_seq: false,
+                tag_width: 0,
+            },
+        )
+    }
+
+    /// Everything of the text layout after the level, so loggers can decorate the level
+    pub(crate) fn write_text_body(&self, writer: &mut dyn Write, layout: &TextLayout) -> io::Result<()> {
+        writeln!(
+            writer,
+            "[{}] {}{}{:tag_width$}{}{}{}{}{}",
+            self.format_timestamp(layout.timestamp_format, layout.use_local_time),
+            SeqMarker(self.seq, layout.show_seq),
+            LatencyMarker(self),
+            TagSegment(self.tag.as_deref().or(layout.default_tag)),
+            ThreadMarker(self),
+            SourceLocation(self, layout.show_source_location),
+            ChunkMarker(self),
+            self.message,
+            TextFields(&self.fields),
+            // the `[]` around the tag and the space after it
+            tag_width = match layout.tag_width {
+                0 => 0,
+                tag_width => tag_width + 3,
+            },
+        )
+    }
+
+    /// Writes a short `I 12:34:56.789 [tag] message` line, leaving out the date and source location
+    pub fn write_compact_to_io(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{} {} [{}] {}{}{}",
+            self.level.to_string().chars().next().unwrap_or('?'),
+            self.timestamp.format("%H:%M:%S%.3f"),
+            self.tag.as_deref().unwrap_or(DEFAULT_TAG),
+            ChunkMarker(self),
+            self.message,
+            TextFields(&self.fields)
+        )
+    }
+
+    /// How long the log waited in the queue, `None` unless `enqueued_at` and `written_at` are set.
+    /// Zero if the clock went backwards in between
+    pub fn write_latency(&self) -> Option<std::time::Duration> {
+        let latency = self.written_at? - self.enqueued_at?;
+        Some(latency.to_std().unwrap_or_default())
+    }
+
+    /// Fills in `thread_id` and `thread_name` from the calling thread, unless already set
+    pub fn capture_thread(&mut self) {
+        if self.thread_id.is_some() {
+            return;
+        }
+
+        let thread = std::thread::current();
+        self.thread_id = Some(thread.id());
+        self.thread_name = thread.name().map(str::to_string);
+    }
+
+    pub(crate) fn format_timestamp(&self, timestamp_format: &str, use_local_time: bool) -> String {
+        match use_local_time {
+            true => self
+                .timestamp
+                .with_timezone(&Local)
+                .format(timestamp_format)
+                .to_string(),
+            false => self.timestamp.format(timestamp_format).to_string(),
+        }
+    }
+
+    /// Writes the log as a single line JSON object, for log ingestion tools
+    pub fn write_json_to_io(&self, writer: &mut dyn Write) -> io::Result<()> {
+        write!(
+            writer,
+            "{{\"level\":\"{}\",\"timestamp\":\"{}\",\"tag\":",
+            self.level,
+            self.timestamp.to_rfc3339()
+        )?;
+        write_json_opt_str(writer, self.tag.as_deref())?;
+        writer.write_all(b",\"file\":")?;
+        write_json_str(writer, &self.file)?;
+        write!(
+            writer,
+            ",\"line\":{},\"column\":{},\"function_name\":",
+            self.line, self.column
+        )?;
+        write_json_opt_str(writer, self.function_name.as_deref())?;
+        if self.seq != 0 {
+            write!(writer, ",\"seq\":{}", self.seq)?;
+        }
+        if let (Some(enqueued_at), Some(written_at)) = (self.enqueued_at, self.written_at) {
+            write!(
+                writer,
+                ",\"enqueued_at\":\"{}\",\"written_at\":\"{}\"",
+                enqueued_at.to_rfc3339(),
+                written_at.to_rfc3339()
+            )?;
+        }
+        if let Some(thread_id) = self.thread_id {
+            writer.write_all(b",\"thread_id\":")?;
+            write_json_str(writer, &format!("{thread_id:?}"))?;
+            writer.write_all(b",\"thread_name\":")?;
+            write_json_opt_str(writer, self.thread_name.as_deref())?;
+        }
+        writer.write_all(b",\"message\":")?;
+        write_json_str(writer, &self.message)?;
+        if self.chunk_total > 1 {
+            write!(
+                writer,
+                ",\"chunk_index\":{},\"chunk_total\":{}",
+                self.chunk_index, self.chunk_total
+            )?;
+        }
+        writer.write_all(b",\"fields\":{")?;
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            write_json_str(writer, key)?;
+            writer.write_all(b":")?;
+            write_json_str(writer, value)?;
+        }
+        writer.write_all(b"}}\n")
+    }
 }
 impl Default for LogData {
     fn default() -> Self {
@@ -59,11 +279,153 @@ impl Default for LogData {
             level: LogLevel::Info,
             tag: None,
             message: String::new(),
-            timestamp: Instant::now(),
-            file: String::new(),
+            timestamp: Utc::now(),
+            file: Cow::Borrowed(""),
             line: 0,
             column: 0,
             function_name: None,
+            thread_id: None,
+            thread_name: None,
+            fields: Vec::new(),
+            chunk_index: 0,
+            chunk_total: 1,
+            seq: 0,
+            enqueued_at: None,
+            written_at: None,
+        }
+    }
+}
+
+/// Formats fields as ` key=value` pairs, nothing if there are none
+struct TextFields<'a>(&'a [(String, String)]);
+
+impl std::fmt::Display for TextFields<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|(key, value)| write!(f, " {key}={value}"))
+    }
+}
+
+/// Formats `#seq `, nothing if turned off or not assigned yet
+struct SeqMarker(u64, bool);
+
+impl std::fmt::Display for SeqMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeqMarker(seq, true) if *seq != 0 => write!(f, "#{seq} "),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Formats `+1.234ms ` between queueing and writing a log, nothing unless both were recorded
+struct LatencyMarker<'a>(&'a LogData);
+
+impl std::fmt::Display for LatencyMarker<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.write_latency() {
+            Some(latency) => write!(f, "+{:.3}ms ", latency.as_secs_f64() * 1000.0),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats `[tag] `, nothing without a tag. Padded with spaces to the formatter's width
+struct TagSegment<'a>(Option<&'a str>);
+
+impl std::fmt::Display for TagSegment<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let len = match self.0 {
+            Some(tag) => {
+                write!(f, "[{tag}] ")?;
+                tag.chars().count() + 3
+            }
+            None => 0,
+        };
+        match f.width() {
+            Some(width) if width > len => write!(f, "{:1$}", "", width - len),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Formats `[tid=ThreadId(7) name=worker-2] `, leaving out the name of unnamed threads
+struct ThreadMarker<'a>(&'a LogData);
+
+impl std::fmt::Display for ThreadMarker<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(thread_id) = self.0.thread_id else {
+            return Ok(());
+        };
+
+        match &self.0.thread_name {
+            Some(name) => write!(f, "[tid={thread_id:?} name={name}] "),
+            None => write!(f, "[tid={thread_id:?}] "),
         }
     }
 }
+
+/// Formats `file:line:column@function `, nothing if turned off
+struct SourceLocation<'a>(&'a LogData, bool);
+
+impl std::fmt::Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (log, show) = (self.0, self.1);
+        if !show {
+            return Ok(());
+        }
+
+        write!(
+            f,
+            "{}:{}:{}@{} ",
+            log.file,
+            log.line,
+            log.column,
+            log.function_name.as_deref().unwrap_or("default")
+        )
+    }
+}
+
+/// Formats `[index/total] ` counting from 1 for split messages, nothing otherwise
+struct ChunkMarker<'a>(&'a LogData);
+
+impl std::fmt::Display for ChunkMarker<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.chunk_total > 1 {
+            true => write!(f, "[{}/{}] ", self.0.chunk_index + 1, self.0.chunk_total),
+            false => Ok(()),
+        }
+    }
+}
+
+fn write_json_opt_str(writer: &mut dyn Write, s: Option<&str>) -> io::Result<()> {
+    match s {
+        Some(s) => write_json_str(writer, s),
+        None => writer.write_all(b"null"),
+    }
+}
+
+/// Writes `s` as a quoted JSON string, escaping quotes, backslashes and control characters
+fn write_json_str(writer: &mut dyn Write, s: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if !matches!(c, '"' | '\\' | '\u{0}'..='\u{1f}') {
+            continue;
+        }
+
+        writer.write_all(&s.as_bytes()[start..i])?;
+        match c {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            '\n' => writer.write_all(b"\\n")?,
+            '\r' => writer.write_all(b"\\r")?,
+            '\t' => writer.write_all(b"\\t")?,
+            c => write!(writer, "\\u{:04x}", c as u32)?,
+        }
+        start = i + c.len_utf8();
+    }
+    writer.write_all(&s.as_bytes()[start..])?;
+
+    writer.write_all(b"\"")
+}
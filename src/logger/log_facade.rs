@@ -0,0 +1,71 @@
+use crate::{log_level::LogLevel, ThreadSafeLoggerThread};
+
+use super::{read_logger, LogData};
+
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => LogLevel::Error,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Debug | log::Level::Trace => LogLevel::Debug,
+        }
+    }
+}
+
+/// Backend for the `log` crate macros, queueing every record on the logger thread
+pub struct PaperLogger {
+    logger_thread: ThreadSafeLoggerThread,
+}
+
+impl PaperLogger {
+    pub fn new(logger_thread: ThreadSafeLoggerThread) -> Self {
+        Self { logger_thread }
+    }
+}
+
+impl log::Log for PaperLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        read_logger(&self.logger_thread).is_level_enabled(metadata.level().into())
+    }
+
+    fn log(&self, record: &log::Record) {
+        let logger_thread = read_logger(&self.logger_thread);
+        let level = record.level().into();
+
+        if !logger_thread.is_level_enabled(level) {
+            return;
+        }
+
+        // `log::Log::log` has no way to report failure
+        let _ = logger_thread.queue_log(LogData {
+            level,
+            tag: Some(record.target().to_string().into()),
+            message: record.args().to_string(),
+            file: match record.file_static() {
+                Some(file) => file.into(),
+                None => record.file().unwrap_or_default().to_string().into(),
+            },
+            line: record.line().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+
+    /// Waits until every record queued so far was written, e.g. before the process exits
+    fn flush(&self) {
+        // `log::Log::flush` has no way to report failure either
+        let _ = read_logger(&self.logger_thread).flush(None);
+    }
+}
+
+/// Installs a [`PaperLogger`] as the global `log` crate logger.
+/// The `log` crate allows a single logger per process, so only one [`LoggerThread`](super::LoggerThread)
+/// can receive its records. Fails if any logger was installed already.
+/// Filtering is left to [`LoggerThread::set_min_level`](super::LoggerThread::set_min_level),
+/// so the `log` max level is set to trace
+pub fn init_log_facade(logger: ThreadSafeLoggerThread) -> Result<(), log::SetLoggerError> {
+    log::set_boxed_logger(Box::new(PaperLogger::new(logger)))?;
+    log::set_max_level(log::LevelFilter::Trace);
+
+    Ok(())
+}
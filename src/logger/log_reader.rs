@@ -0,0 +1,183 @@
+use std::io::BufRead;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use color_eyre::eyre::{eyre, OptionExt};
+
+use crate::Result;
+
+use super::{
+    log_data::{DEFAULT_TAG, DEFAULT_TIMESTAMP_FORMAT},
+    LogData,
+};
+
+///
+/// Reads logs written in the text layout back into [`LogData`], for tooling filtering
+/// old logs by level or tag.
+///
+/// Lines which don't start like a log are continuations of a multi-line message and
+/// are appended to the previous log. Some of the layout doesn't survive the round trip:
+/// - untagged logs were written with the default tag, they come back with `tag: None`
+/// - `key=value` fields can't be told apart from the message, they stay part of it
+/// - thread ids can't be recreated, only the thread name is kept
+/// - the `host=.. pid=..` origin has no place in a [`LogData`] and is dropped
+/// - the `+1.234ms` write latency is dropped, as the times it was taken from are gone
+pub struct LogReader<R> {
+    lines: std::io::Lines<R>,
+    timestamp_format: String,
+    use_local_time: bool,
+    pending: Option<LogData>,
+}
+
+impl<R: BufRead> LogReader<R> {
+    /// Reads logs written with the default timestamp format, in UTC
+    pub fn new(reader: R) -> Self {
+        Self::with_timestamp_format(reader, DEFAULT_TIMESTAMP_FORMAT, false)
+    }
+
+    /// Reads logs written with `LoggerConfig::timestamp_format` and `LoggerConfig::use_local_time`
+    pub fn with_timestamp_format(reader: R, timestamp_format: &str, use_local_time: bool) -> Self {
+        Self {
+            lines: reader.lines(),
+            timestamp_format: timestamp_format.to_string(),
+            use_local_time,
+            pending: None,
+        }
+    }
+
+    /// Parses `INFO [time] #seq +latency [tag] [tid=.. name=..] file:line:column@function [i/n] message`,
+    /// `None` if the line doesn't start with a level and timestamp.
+    /// A `host=.. pid=..` origin in front of the level is skipped
+    fn parse_line(&self, line: &str) -> Option<Result<LogData>> {
+        let (level, rest) = line.split_once(" [")?;
+        // aligned levels are padded with spaces
+        let level = level.trim_end().rsplit(' ').next()?.parse().ok()?;
+        let (timestamp, rest) = rest.split_once("] ")?;
+        let timestamp = self.parse_timestamp(timestamp).ok()?;
+
+        Some(parse_body(rest).map(|log| LogData {
+            level,
+            timestamp,
+            ..log
+        }))
+    }
+
+    fn parse_timestamp(&self, timestamp: &str) -> Result<DateTime<Utc>> {
+        let naive = NaiveDateTime::parse_from_str(timestamp, &self.timestamp_format)?;
+
+        match self.use_local_time {
+            true => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|timestamp| timestamp.with_timezone(&Utc))
+                .ok_or_eyre("Timestamp doesn't exist in the local timezone"),
+            false => Ok(naive.and_utc()),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LogReader<R> {
+    type Item = Result<LogData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e.into())),
+                None => return self.pending.take().map(Ok),
+            };
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+
+            match self.parse_line(line) {
+                Some(Ok(log)) => {
+                    if let Some(previous) = self.pending.replace(log) {
+                        return Some(Ok(previous));
+                    }
+                }
+                Some(Err(e)) => return Some(Err(e.wrap_err(format!("Malformed log line: {line}")))),
+                None => match &mut self.pending {
+                    Some(log) => {
+                        log.message.push('\n');
+                        log.message.push_str(line);
+                    }
+                    None => return Some(Err(eyre!("Log line without a preceding log: {line}"))),
+                },
+            }
+        }
+    }
+}
+
+/// Everything after the timestamp
+fn parse_body(mut rest: &str) -> Result<LogData> {
+    let mut log = LogData::default();
+
+    if let Some((seq, after)) = rest.strip_prefix('#').and_then(|after| after.split_once(' ')) {
+        if let Ok(seq) = seq.parse() {
+            log.seq = seq;
+            rest = after;
+        }
+    }
+
+    if let Some((_, after)) = rest
+        .strip_prefix('+')
+        .and_then(|after| after.split_once("ms "))
+        .filter(|(latency, _)| latency.parse::<f64>().is_ok())
+    {
+        rest = after;
+    }
+
+    if let Some(after) = rest.strip_prefix('[').filter(|_| !rest.starts_with("[tid=")) {
+        let (tag, after) = after.split_once("] ").ok_or_eyre("Unterminated tag")?;
+        if tag != DEFAULT_TAG {
+            log.tag = Some(tag.to_string().into());
+        }
+        rest = after;
+    }
+    // padding of an aligned tag
+    rest = rest.trim_start_matches(' ');
+
+    if let Some(after) = rest.strip_prefix("[tid=") {
+        let (thread, after) = after.split_once("] ").ok_or_eyre("Unterminated thread marker")?;
+        log.thread_name = thread.split_once(" name=").map(|(_, name)| name.to_string());
+        rest = after;
+    }
+
+    if let Some(after) = parse_location(rest, &mut log) {
+        rest = after;
+    }
+
+    if let Some((marker, after)) = rest.strip_prefix('[').and_then(|after| after.split_once("] ")) {
+        if let Some((index, total)) = marker.split_once('/') {
+            if let (Ok(index), Ok(total)) = (index.parse::<usize>(), total.parse::<usize>()) {
+                if index >= 1 && total > 1 {
+                    log.chunk_index = index - 1;
+                    log.chunk_total = total;
+                    rest = after;
+                }
+            }
+        }
+    }
+
+    log.message = rest.to_string();
+    Ok(log)
+}
+
+/// Fills in `file:line:column@function ` if `rest` starts with it, returning the remainder
+fn parse_location<'a>(rest: &'a str, log: &mut LogData) -> Option<&'a str> {
+    let (location, after) = rest.split_once('@')?;
+    let (function_name, after) = after.split_once(' ').unwrap_or((after, ""));
+
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?;
+
+    log.file = file.to_string().into();
+    log.line = line;
+    log.column = column;
+    log.function_name = match function_name {
+        "default" => None,
+        function_name => Some(function_name.to_string()),
+    };
+
+    Some(after)
+}
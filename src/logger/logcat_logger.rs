@@ -2,6 +2,8 @@
 
 use crate::{log_level::LogLevel, Result};
 
+use super::log_data::DEFAULT_TAG;
+
 use std::ffi::CString;
 
 // assert tracing is not enabled
@@ -50,6 +52,11 @@ pub enum Buffer {
     Radio = log_id::LOG_ID_RADIO.0,
 }
 
+/// logcat cuts off anything past roughly 4076 bytes per line, leave room for its header
+const MAX_LINE_LEN: usize = 4000;
+/// Longer tags are rejected by `__android_log_is_loggable` on older Android versions
+const MAX_TAG_LEN: usize = 23;
+
 impl From<LogLevel> for Priority {
     fn from(level: LogLevel) -> Self {
         match level {
@@ -72,13 +79,14 @@ pub(crate) fn do_log(log: &super::log_data::LogData) -> Result<()> {
         log.message.clone()
     );
 
-    let priority: Priority = log.level.clone().into();
-    let tag = CString::new(log.tag.as_deref().unwrap_or("default"))?;
+    let priority: Priority = log.level.into();
+    let tag = CString::new(truncate_on_char_boundary(
+        log.tag.as_deref().unwrap_or(DEFAULT_TAG),
+        MAX_TAG_LEN,
+    ))?;
     let file = CString::new(log.file.to_string())?;
-    let message = CString::new(message_str)?;
     // tag, priority, and time are provided by android's logcat
 
-
     #[cfg(feature = "android-api-30")]
     {
         use ndk_sys::{__android_log_message, __android_log_write_log_message};
@@ -87,24 +95,30 @@ pub(crate) fn do_log(log: &super::log_data::LogData) -> Result<()> {
             return Ok(());
         }
 
-        let mut message = __android_log_message {
-            struct_size: size_of::<__android_log_message>(),
-            buffer_id: Buffer::Default as i32,
-            priority: priority as i32,
-            tag: tag.as_ptr(),
-            file: file.as_ptr(),
-            line: log.line,
-            message: message.as_ptr(),
-        };
-
-        unsafe { __android_log_write_log_message(&mut message) };
+        for line in split_on_char_boundary(&message_str, MAX_LINE_LEN) {
+            let line = CString::new(line)?;
+            let mut message = __android_log_message {
+                struct_size: size_of::<__android_log_message>(),
+                buffer_id: Buffer::Default as i32,
+                priority: priority as i32,
+                tag: tag.as_ptr(),
+                file: file.as_ptr(),
+                line: log.line,
+                message: line.as_ptr(),
+            };
+
+            unsafe { __android_log_write_log_message(&mut message) };
+        }
     }
 
     #[cfg(not(feature = "android-api-30"))]
     {
         use ndk_sys::__android_log_buf_write;
 
-        unsafe { __android_log_buf_write(Buffer::Default as i32, priority as i32, tag.as_ptr(), message.as_ptr()) };
+        for line in split_on_char_boundary(&message_str, MAX_LINE_LEN) {
+            let line = CString::new(line)?;
+            unsafe { __android_log_buf_write(Buffer::Default as i32, priority as i32, tag.as_ptr(), line.as_ptr()) };
+        }
     }
 
     Ok(())
@@ -118,3 +132,31 @@ pub(crate) fn do_log(log: &super::log_data::LogData) -> Result<()> {
     //     }
     // }
 }
+
+/// The longest prefix of `s` within `max_len` bytes which doesn't cut a char in half
+fn truncate_on_char_boundary(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+
+    let end = (0..=max_len).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+    &s[..end]
+}
+
+/// Splits `s` into pieces of at most `max_len` bytes, each ending on a char boundary
+fn split_on_char_boundary(s: &str, max_len: usize) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    let mut first = true;
+
+    std::iter::from_fn(move || {
+        // an empty message is still written once
+        if rest.is_empty() && !std::mem::take(&mut first) {
+            return None;
+        }
+        first = false;
+
+        let piece = truncate_on_char_boundary(rest, max_len);
+        rest = &rest[piece.len()..];
+        Some(piece)
+    })
+}
@@ -1,19 +1,21 @@
 use std::{
     backtrace::Backtrace,
-    collections::HashMap,
-    fs::{self, File},
-    io::{BufWriter, Write},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::Write,
     panic::PanicHookInfo,
     path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, Weak,
     },
-    thread,
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
 use crate::{log_level::LogLevel, semaphore_lite::SemaphoreLite, Result};
+use chrono::{DateTime, Local, Utc};
 use color_eyre::eyre::{bail, eyre, Context};
 use itertools::Itertools;
 
@@ -22,6 +24,8 @@ pub mod logcat_logger;
 
 #[cfg(feature = "file")]
 pub mod file_logger;
+#[cfg(feature = "file")]
+use file_logger::{GlobalOutput, LogFile};
 
 #[cfg(feature = "stdout")]
 pub mod stdout_logger;
@@ -32,264 +36,2451 @@ pub mod sink_logger;
 #[cfg(feature = "tracing")]
 pub mod tracing_logger;
 
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+
+#[cfg(feature = "log-compat")]
+pub mod log_facade;
+
+#[cfg(feature = "syslog")]
+pub mod syslog_sink;
+
+#[cfg(feature = "tcp")]
+mod tcp_sink;
+
+#[cfg(all(windows, feature = "wineventlog"))]
+mod eventlog_sink;
+
+#[cfg(feature = "test-util")]
+mod test_sink;
+#[cfg(feature = "test-util")]
+pub use test_sink::TestSink;
+
+#[cfg(feature = "sinks")]
+mod async_sink;
+#[cfg(feature = "sinks")]
+use async_sink::AsyncSink;
+
+mod dedup;
+use dedup::{Deduplicator, DEDUP_TIMEOUT};
+
+mod sampling;
+
+mod ring_buffer;
+use ring_buffer::RingBuffer;
+
+mod rate_limit;
+use rate_limit::{RateLimitDecision, RateLimiter};
+
+mod field_value;
+pub use field_value::FieldValue;
+
+mod formatter;
+#[cfg(feature = "stdout")]
+pub use formatter::LevelColors;
+pub use formatter::{CompactFormatter, DefaultFormatter, JsonFormatter, LogFormatter};
+
+mod template;
+pub use template::TemplateFormatter;
+
 mod log_data;
 pub use log_data::LogData;
+use log_data::{is_valid_timestamp_format, DEFAULT_TAG, DEFAULT_TIMESTAMP_FORMAT};
+
+mod log_reader;
+pub use log_reader::LogReader;
 
 pub trait LogCallback: Fn(&LogData) -> Result<()> + Send + Sync {}
+impl<F> LogCallback for F where F: Fn(&LogData) -> Result<()> + Send + Sync {}
+
+/// A log together with its line as the logger's formatter renders it, ending with
+/// `config.line_ending`. The line is formatted once per log and shared by every sink
+#[derive(Debug, Clone)]
+pub struct FormattedLog<'a> {
+    pub log: &'a LogData,
+    pub line: Arc<str>,
+}
+
+/// A sink for [`LoggerThread::add_formatted_sink`]
+pub trait FormattedLogCallback: Fn(&FormattedLog) -> Result<()> + Send + Sync {}
+impl<F> FormattedLogCallback for F where F: Fn(&FormattedLog) -> Result<()> + Send + Sync {}
+
+/// What a [`LogSink`] did with a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkStatus {
+    /// The log was handled
+    Accepted,
+    /// The sink can't take the log right now, e.g. a momentarily full network buffer.
+    /// It is handed over again after a backoff, and dropped after a few attempts
+    Retry,
+    /// The sink gave up on the log, it counts towards [`LoggerThread::async_sink_dropped_count`]
+    Dropped,
+}
+
+/// A sink for [`LoggerThread::add_async_sink`] which can signal backpressure.
+/// Every [`LogCallback`] closure is a `LogSink` that drops the logs it returns an error for
+pub trait LogSink: Send + Sync {
+    fn send(&self, log: &LogData) -> SinkStatus;
+}
+
+impl<F> LogSink for F
+where
+    F: LogCallback,
+{
+    fn send(&self, log: &LogData) -> SinkStatus {
+        match self(log) {
+            Ok(()) => SinkStatus::Accepted,
+            Err(_) => SinkStatus::Dropped,
+        }
+    }
+}
 
 pub type ThreadSafeLoggerThread = Arc<RwLock<LoggerThread>>;
 
+/// Layout used by the file and stdout loggers
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable text, see [`LogData::format`]
+    #[default]
+    Text,
+    /// One JSON object per line, see [`LogData::write_json_to_io`]
+    Json,
+    /// Short lines without date and source location, see [`LogData::write_compact_to_io`]
+    Compact,
+}
+
+impl LogFormat {
+    /// The formatter for this format, using the timestamp settings of `config` for text
+    pub fn formatter(&self, config: &LoggerConfig) -> Arc<dyn LogFormatter> {
+        match self {
+            // an invalid template is rejected by `LoggerConfig::validate`, which the logger runs first
+            LogFormat::Text => match config
+                .line_template
+                .as_deref()
+                .and_then(|template| TemplateFormatter::new(template, config).ok())
+            {
+                Some(formatter) => Arc::new(formatter),
+                None => Arc::new(DefaultFormatter {
+                    timestamp_format: config.timestamp_format.clone(),
+                    use_local_time: config.use_local_time,
+                    default_tag: config.default_tag.clone(),
+                    show_source_location: config.show_source_location,
+                    show_seq: config.show_seq,
+                    align_columns: config.align_columns,
+                    tag_width: config.tag_width,
+                    origin: line_origin(config),
+                    #[cfg(feature = "stdout")]
+                    level_colors: config.level_colors,
+                }),
+            },
+            LogFormat::Json => Arc::new(JsonFormatter),
+            LogFormat::Compact => Arc::new(CompactFormatter),
+        }
+    }
+
+    /// Writes `log` in this format, using the timestamp settings of `config` for text
+    ///
+    /// Builds the formatter on every call, `LoggerThread::write_log` reuses the one built at init
+    pub fn write(&self, log: &LogData, config: &LoggerConfig, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.formatter(config).format(log, writer)
+    }
+}
+
+/// `host=name pid=N` as far as `config` asks for them, the hostname is looked up once per process
+fn line_origin(config: &LoggerConfig) -> Option<String> {
+    let mut origin = Vec::new();
+
+    #[cfg(feature = "hostname")]
+    if config.include_hostname {
+        if let Some(hostname) = hostname() {
+            origin.push(format!("host={hostname}"));
+        }
+    }
+    if config.include_pid {
+        origin.push(format!("pid={}", std::process::id()));
+    }
+
+    match origin.is_empty() {
+        true => None,
+        false => Some(origin.join(" ")),
+    }
+}
+
+/// Name of this machine, looked up once per process
+#[cfg(feature = "hostname")]
+fn hostname() -> Option<&'static str> {
+    static HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
+    HOSTNAME
+        .get_or_init(|| hostname::get().ok().map(|name| name.to_string_lossy().into_owned()))
+        .as_deref()
+}
+
+/// How the file and stdout loggers terminate each written line
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Replaces the `\n` a formatter ended `line` with
+    pub(crate) fn terminate(&self, line: &mut Vec<u8>) {
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        line.extend_from_slice(self.as_str().as_bytes());
+    }
+}
+
+/// Whether the stdout logger colors the level of each line
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Color only if stdout is a terminal. `NO_COLOR`, `CLICOLOR_FORCE` and `CLICOLOR=0`
+    /// take precedence over the terminal check
+    #[default]
+    Auto,
+}
+
+/// What happens to lines longer than `max_string_len`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongMessagePolicy {
+    /// Continue the line in further chunks
+    #[default]
+    Split,
+    /// Cut the line to `max_string_len`
+    Truncate,
+    /// Cut the line and end it with `…`, keeping it within `max_string_len`
+    TruncateWithEllipsis,
+}
+
+/// When the global and context files are rolled over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RotationPolicy {
+    #[default]
+    None,
+    /// Once a file exceeds this many bytes, shifting it to `name.1.log`, `name.2.log` and so on
+    Size(u64),
+    /// When the day changes, moving the file to `name-2024-06-01.log`
+    Daily,
+    /// When the hour changes, moving the file to `name-2024-06-01-13.log`
+    Hourly,
+}
+
+impl RotationPolicy {
+    /// Suffix of the calendar period `time` falls into, `None` unless rolling over by time
+    pub(crate) fn period(&self, time: DateTime<Utc>, use_local_time: bool) -> Option<String> {
+        let format = match self {
+            RotationPolicy::Daily => "%Y-%m-%d",
+            RotationPolicy::Hourly => "%Y-%m-%d-%H",
+            RotationPolicy::None | RotationPolicy::Size(_) => return None,
+        };
+
+        Some(match use_local_time {
+            true => time.with_timezone(&Local).format(format).to_string(),
+            false => time.format(format).to_string(),
+        })
+    }
+}
+
+/// Which standard stream the stdout logger writes each level to
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdStreamPolicy {
+    #[default]
+    AllStdout,
+    /// Errors and warnings to stderr, everything else to stdout
+    ErrWarnToStderr,
+    AllStderr,
+}
+
+/// What [`LoggerThread::queue_log`] does once the queue holds `log_max_buffer_count` logs
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the writer thread to drain the queue
+    #[default]
+    Block,
+    /// Drop the oldest queued log to make room
+    DropOldest,
+    /// Drop the log being queued
+    DropNewest,
+}
+
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct LoggerConfig {
     pub max_string_len: usize,
+    /// Whether lines longer than `max_string_len` are split or truncated.
+    /// Messages are split into lines on `line_end` either way
+    pub long_message_policy: LongMessagePolicy,
+    /// Maximum amount of logs waiting in the queue, 0 for unbounded
     pub log_max_buffer_count: usize,
+    pub overflow_policy: OverflowPolicy,
+    /// Logs which waited in the queue for longer are dropped by the writer thread instead of
+    /// written, so it catches up on fresh logs after a stall. `None` writes every log however old.
+    /// Guaranteed tags are never dropped, see [`LoggerThread::set_guaranteed_tag`]
+    pub max_queue_age: Option<Duration>,
+    /// Collapse consecutive identical logs into a "last message repeated N times" line
+    pub dedup: bool,
+    /// How many logs each async sink may lag behind before logs are dropped for it
+    pub async_sink_buffer: usize,
+    /// Sinks which fail this many times in a row stop receiving logs, 0 to keep calling them
+    pub sink_failure_limit: usize,
+    /// Sinks taking longer than this to handle a log are reported with a warning, at most once
+    /// a minute each. Timing costs two clock reads per sink call, `None` skips it
+    pub slow_sink_threshold: Option<Duration>,
+    /// Flush the files at least this often while logs keep coming in
+    pub flush_interval: Duration,
+    /// Flush the files once this many logs were written since the last flush
+    pub flush_log_count: usize,
+    /// Flush the files right after writing a log at or above this level, so it survives
+    /// the process dying moments later
+    pub flush_on_level: Option<LogLevel>,
+    /// Most logs the writer thread handles before checking whether the files are due a flush,
+    /// 0 for everything queued at once. Bounds how long a burst can hold off the interval flush
+    pub max_drain_batch: usize,
+    /// Incoming messages are split into lines on this character.
+    /// When it is `\n`, a `\r` in front of it is dropped as well
     pub line_end: char,
+    /// Terminator of every written line
+    pub line_ending: LineEnding,
+    pub log_format: LogFormat,
+    /// Layout of text lines with placeholders like `"{level} {time} {tag}: {message}"`, replacing
+    /// the default text layout. See [`TemplateFormatter`] for the placeholders
+    pub line_template: Option<String>,
+    /// Chunk long lines on grapheme cluster boundaries instead of chars.
+    /// Requires the `graphemes` feature, otherwise chars are always used
+    pub split_on_graphemes: bool,
+    /// Escape control chars in messages, e.g. `ESC` as `\u{1b}` and a stray `\r` as `\r`, so
+    /// untrusted input can't rewrite the terminal or forge log lines. Tabs are kept and `line_end`
+    /// splits the message into lines as usual. Done by the writer thread, `queue_log` stays as cheap
+    pub sanitize_control_chars: bool,
+    /// Logs below this level are dropped before they reach the queue
+    pub min_level: LogLevel,
+    /// Fraction of logs to keep per level, from 0.0 to 1.0. Levels without an entry are never sampled
+    pub sample_rate: HashMap<LogLevel, f64>,
+    /// chrono strftime format of the timestamp in text logs.
+    /// Subsecond precision is set with `%.3f` (millis), `%.6f` (micros) or `%.9f` (nanos)
+    pub timestamp_format: String,
+    /// Print text log timestamps in the local timezone instead of UTC
+    pub use_local_time: bool,
+    /// Tag shown for untagged logs in the text format, the `[tag]` segment is left out if `None`
+    pub default_tag: Option<String>,
+    /// Include the `file:line:column@function` block in text logs
+    pub show_source_location: bool,
+    /// Include the `#seq` of each log in text logs, see [`LogData::seq`]
+    pub show_seq: bool,
+    /// Pad the level and tag of text logs to fixed widths, so the columns after them line up
+    pub align_columns: bool,
+    /// Chars tags are padded to with `align_columns`
+    pub tag_width: usize,
+    /// Record when each log was queued and written, showing how long it waited as `+1.234ms`
+    /// in text logs. Helps telling whether the writer thread falls behind, at the cost of
+    /// reading the clock twice per log. See [`LogData::enqueued_at`]
+    pub track_write_latency: bool,
+    /// Start text log lines with `pid=N`, to tell apart logs of several processes once merged
+    pub include_pid: bool,
+    /// Start text log lines with `host=name`. Requires the `hostname` feature, otherwise ignored
+    pub include_hostname: bool,
+    /// Start the log with a line summarizing the version, level, file and enabled features
+    pub log_startup_banner: bool,
+
+    #[cfg(feature = "stdout")]
+    pub color: ColorMode,
+    /// Colors of the level names when `color` allows them
+    #[cfg(feature = "stdout")]
+    pub level_colors: LevelColors,
+    #[cfg(feature = "stdout")]
+    pub std_stream: StdStreamPolicy,
 
     #[cfg(feature = "file")]
     pub context_log_path: PathBuf,
+    /// When to roll the global and context files over. Periods follow `use_local_time`
+    #[cfg(feature = "file")]
+    pub rotation: RotationPolicy,
+    /// How many rolled over files (`name.1.log`, ... or `name-2024-06-01.log`, ...) to keep around
+    #[cfg(feature = "file")]
+    pub max_files: usize,
+    /// Gzip rotated files to `name.1.log.gz`. Requires the `compression` feature
+    #[cfg(feature = "file")]
+    pub compress_rotated: bool,
+    /// Gzip the global, context and error files as they are written, to `name.log.gz`.
+    /// Saves space at the cost of tailing them, flushes keep what was written so far readable.
+    /// Requires the `compression` feature, otherwise ignored
+    #[cfg(feature = "file")]
+    pub compress_active: bool,
+    /// Append to existing global and context files instead of truncating them,
+    /// keeping the logs of previous runs
+    #[cfg(feature = "file")]
+    pub append: bool,
+    /// Keep logging without files if the log directory or global file can't be created,
+    /// instead of failing [`LoggerThread::new`]. The error is printed to stderr.
+    /// The global file's lines go to stdout unless the stdout logger prints them already
+    #[cfg(feature = "file")]
+    pub fallback_to_stdout_on_file_error: bool,
+    /// Bytes buffered per log file before they are written out. Larger means fewer syscalls,
+    /// smaller means less waiting on a flush to see the logs
+    #[cfg(feature = "file")]
+    pub write_buffer_capacity: usize,
+    /// Write and flush every line to the files on its own, so `tail -f` is always up to date
+    /// and a crash loses nothing already written. Much slower than the buffered default
+    #[cfg(feature = "file")]
+    pub flush_every_line: bool,
+    /// File which additionally receives every log at or above `error_log_level`, whatever its tag,
+    /// e.g. `logs/errors.log`. Rotated like the global file
+    #[cfg(feature = "file")]
+    pub error_log_path: Option<PathBuf>,
+    #[cfg(feature = "file")]
+    pub error_log_level: LogLevel,
 }
 
 impl Default for LoggerConfig {
     fn default() -> Self {
         LoggerConfig {
             max_string_len: 1024,
+            long_message_policy: LongMessagePolicy::Split,
             log_max_buffer_count: 100,
+            overflow_policy: OverflowPolicy::Block,
+            max_queue_age: None,
+            dedup: false,
+            async_sink_buffer: 1024,
+            sink_failure_limit: 5,
+            slow_sink_threshold: None,
+            flush_interval: Duration::from_secs(1),
+            flush_log_count: 50,
+            flush_on_level: None,
+            max_drain_batch: 0,
             line_end: '\n',
+            line_ending: LineEnding::Lf,
+            log_format: LogFormat::Text,
+            line_template: None,
+            split_on_graphemes: false,
+            sanitize_control_chars: false,
+            min_level: LogLevel::Debug,
+            sample_rate: HashMap::new(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            use_local_time: false,
+            default_tag: Some(DEFAULT_TAG.to_string()),
+            show_source_location: true,
+            show_seq: false,
+            align_columns: false,
+            tag_width: formatter::DEFAULT_TAG_WIDTH,
+            track_write_latency: false,
+            include_pid: false,
+            include_hostname: false,
+            log_startup_banner: false,
+
+            #[cfg(feature = "stdout")]
+            color: ColorMode::Auto,
+            #[cfg(feature = "stdout")]
+            level_colors: LevelColors::default(),
+            #[cfg(feature = "stdout")]
+            std_stream: StdStreamPolicy::AllStdout,
 
             #[cfg(feature = "file")]
             context_log_path: PathBuf::from("./logs"),
+            #[cfg(feature = "file")]
+            rotation: RotationPolicy::None,
+            #[cfg(feature = "file")]
+            max_files: 5,
+            #[cfg(feature = "file")]
+            compress_rotated: false,
+            #[cfg(feature = "file")]
+            compress_active: false,
+            #[cfg(feature = "file")]
+            append: false,
+            #[cfg(feature = "file")]
+            fallback_to_stdout_on_file_error: false,
+            #[cfg(feature = "file")]
+            write_buffer_capacity: 8 * 1024,
+            #[cfg(feature = "file")]
+            flush_every_line: false,
+            #[cfg(feature = "file")]
+            error_log_path: None,
+            #[cfg(feature = "file")]
+            error_log_level: LogLevel::Error,
         }
     }
 }
 
-pub struct LoggerThread {
-    pub config: LoggerConfig,
+impl LoggerConfig {
+    pub fn builder() -> LoggerConfigBuilder {
+        LoggerConfigBuilder::default()
+    }
 
-    log_queue: Arc<(SemaphoreLite, Mutex<Vec<LogData>>)>,
-    flush_semaphore: Arc<SemaphoreLite>,
+    /// Sets `min_level` from the environment variable `var`, e.g. `PAPERLOG_LEVEL=debug` or `PAPERLOG_LEVEL=W`.
+    /// `min_level` is left alone if `var` isn't set, an unparsable value is an error
+    pub fn min_level_from_env(&mut self, var: &str) -> Result<()> {
+        let Some(value) = std::env::var_os(var) else {
+            return Ok(());
+        };
+        let value = value
+            .into_string()
+            .map_err(|value| eyre!("{var} is not valid unicode: {value:?}"))?;
+        self.min_level = value.parse().with_context(|| format!("Invalid log level in {var}"))?;
+        Ok(())
+    }
 
-    inited: AtomicBool,
+    /// Rejects a zero `max_string_len`, unparsable timestamp formats and line templates,
+    /// and sample rates outside of 0.0..=1.0
+    pub fn validate(&self) -> Result<()> {
+        if self.max_string_len == 0 {
+            bail!("max_string_len must be greater than 0");
+        }
+        if !is_valid_timestamp_format(&self.timestamp_format) {
+            bail!("Invalid timestamp format {:?}", self.timestamp_format);
+        }
+        if let Some(template) = &self.line_template {
+            TemplateFormatter::new(template, self)?;
+        }
+        if self.max_queue_age.is_some_and(|max_queue_age| max_queue_age.is_zero()) {
+            bail!("max_queue_age must be greater than 0, use None to keep every log");
+        }
+        if let Some((level, rate)) = self.sample_rate.iter().find(|(_, rate)| !(0.0..=1.0).contains(*rate)) {
+            bail!("Sample rate {rate} of {level} is outside of 0.0..=1.0");
+        }
 
-    #[cfg(feature = "file")]
-    global_file: BufWriter<File>,
+        Ok(())
+    }
+}
 
-    #[cfg(feature = "file")]
-    context_map: HashMap<String, BufWriter<File>>,
+/// Chainable construction of a [`LoggerConfig`].
+/// Setters for file only options are accepted and ignored without the `file` feature
+#[derive(Debug, Clone)]
+pub struct LoggerConfigBuilder {
+    config: LoggerConfig,
+}
 
-    sinks: Vec<Box<dyn LogCallback>>,
+impl Default for LoggerConfigBuilder {
+    fn default() -> Self {
+        Self {
+            config: LoggerConfig {
+                max_string_len: 4096,
+                log_max_buffer_count: 50,
+                line_end: '\n',
+                ..Default::default()
+            },
+        }
+    }
 }
 
-impl LoggerThread {
-    pub fn new(config: LoggerConfig, log_path: PathBuf) -> Result<Self> {
-        let log_queue = Arc::new((SemaphoreLite::new(), Mutex::new(Vec::with_capacity(config.log_max_buffer_count))));
-        let flush_semaphore = Arc::new(SemaphoreLite::new());
+impl LoggerConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        #[cfg(feature = "file")]
-        let global_file = {
-            fs::create_dir_all(&config.context_log_path).with_context(|| {
-                format!(
-                    "Unable to make logging directory for contexts {}",
-                    config.context_log_path.display()
-                )
-            })?;
-
-            if let Some(parent) = log_path.parent() {
-                fs::create_dir_all(parent).with_context(|| {
-                    format!(
-                        "Unable to make logging directory for global file {}",
-                        config.context_log_path.display()
-                    )
-                })?;
-            }
+    pub fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.config.max_string_len = max_string_len;
+        self
+    }
 
-            let inner = File::create(&log_path).map_err(|e| {
-                eyre!(
-                    "Unable to create global file at {}: {}",
-                    log_path.display(),
-                    e.to_string()
-                )
-            })?;
-            BufWriter::new(inner)
-        };
+    pub fn long_message_policy(mut self, long_message_policy: LongMessagePolicy) -> Self {
+        self.config.long_message_policy = long_message_policy;
+        self
+    }
 
-        Ok(LoggerThread {
-            config,
-            log_queue,
-            flush_semaphore,
-            inited: AtomicBool::new(false),
+    pub fn log_max_buffer_count(mut self, log_max_buffer_count: usize) -> Self {
+        self.config.log_max_buffer_count = log_max_buffer_count;
+        self
+    }
 
-            #[cfg(feature = "file")]
-            global_file,
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.config.overflow_policy = overflow_policy;
+        self
+    }
 
-            #[cfg(feature = "file")]
-            context_map: HashMap::new(),
+    pub fn max_queue_age(mut self, max_queue_age: Option<Duration>) -> Self {
+        self.config.max_queue_age = max_queue_age;
+        self
+    }
 
-            sinks: Vec::new(),
-        })
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.config.dedup = dedup;
+        self
     }
 
-    pub fn init(self, install_panic_hook: bool) -> Result<ThreadSafeLoggerThread> {
-        if self.inited.load(Ordering::SeqCst) {
-            bail!("LoggerThread already initialized");
+    pub fn async_sink_buffer(mut self, async_sink_buffer: usize) -> Self {
+        self.config.async_sink_buffer = async_sink_buffer;
+        self
+    }
+
+    pub fn sink_failure_limit(mut self, sink_failure_limit: usize) -> Self {
+        self.config.sink_failure_limit = sink_failure_limit;
+        self
+    }
+
+    pub fn slow_sink_threshold(mut self, slow_sink_threshold: Option<Duration>) -> Self {
+        self.config.slow_sink_threshold = slow_sink_threshold;
+        self
+    }
+
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.config.flush_interval = flush_interval;
+        self
+    }
+
+    pub fn flush_log_count(mut self, flush_log_count: usize) -> Self {
+        self.config.flush_log_count = flush_log_count;
+        self
+    }
+
+    pub fn flush_on_level(mut self, flush_on_level: Option<LogLevel>) -> Self {
+        self.config.flush_on_level = flush_on_level;
+        self
+    }
+
+    pub fn max_drain_batch(mut self, max_drain_batch: usize) -> Self {
+        self.config.max_drain_batch = max_drain_batch;
+        self
+    }
+
+    pub fn line_end(mut self, line_end: char) -> Self {
+        self.config.line_end = line_end;
+        self
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.config.line_ending = line_ending;
+        self
+    }
+
+    pub fn log_format(mut self, log_format: LogFormat) -> Self {
+        self.config.log_format = log_format;
+        self
+    }
+
+    pub fn split_on_graphemes(mut self, split_on_graphemes: bool) -> Self {
+        self.config.split_on_graphemes = split_on_graphemes;
+        self
+    }
+
+    pub fn sanitize_control_chars(mut self, sanitize_control_chars: bool) -> Self {
+        self.config.sanitize_control_chars = sanitize_control_chars;
+        self
+    }
+
+    pub fn min_level(mut self, min_level: LogLevel) -> Self {
+        self.config.min_level = min_level;
+        self
+    }
+
+    pub fn sample_rate(mut self, level: LogLevel, rate: f64) -> Self {
+        self.config.sample_rate.insert(level, rate);
+        self
+    }
+
+    pub fn timestamp_format(mut self, timestamp_format: impl Into<String>) -> Self {
+        self.config.timestamp_format = timestamp_format.into();
+        self
+    }
+
+    pub fn use_local_time(mut self, use_local_time: bool) -> Self {
+        self.config.use_local_time = use_local_time;
+        self
+    }
+
+    pub fn default_tag(mut self, default_tag: Option<String>) -> Self {
+        self.config.default_tag = default_tag;
+        self
+    }
+
+    pub fn show_source_location(mut self, show_source_location: bool) -> Self {
+        self.config.show_source_location = show_source_location;
+        self
+    }
+
+    /// See [`LoggerConfig::line_template`], an invalid template fails [`LoggerConfigBuilder::build`]
+    pub fn line_template(mut self, line_template: impl Into<String>) -> Self {
+        self.config.line_template = Some(line_template.into());
+        self
+    }
+
+    pub fn show_seq(mut self, show_seq: bool) -> Self {
+        self.config.show_seq = show_seq;
+        self
+    }
+
+    pub fn align_columns(mut self, align_columns: bool) -> Self {
+        self.config.align_columns = align_columns;
+        self
+    }
+
+    pub fn tag_width(mut self, tag_width: usize) -> Self {
+        self.config.tag_width = tag_width;
+        self
+    }
+
+    pub fn track_write_latency(mut self, track_write_latency: bool) -> Self {
+        self.config.track_write_latency = track_write_latency;
+        self
+    }
+
+    pub fn include_pid(mut self, include_pid: bool) -> Self {
+        self.config.include_pid = include_pid;
+        self
+    }
+
+    pub fn include_hostname(mut self, include_hostname: bool) -> Self {
+        self.config.include_hostname = include_hostname;
+        self
+    }
+
+    pub fn log_startup_banner(mut self, log_startup_banner: bool) -> Self {
+        self.config.log_startup_banner = log_startup_banner;
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn color(mut self, color: ColorMode) -> Self {
+        #[cfg(feature = "stdout")]
+        {
+            self.config.color = color;
         }
+        self
+    }
 
-        self.inited.store(true, Ordering::SeqCst);
+    #[cfg(feature = "stdout")]
+    pub fn level_colors(mut self, level_colors: LevelColors) -> Self {
+        self.config.level_colors = level_colors;
+        self
+    }
 
-        let log_queue_clone = Arc::clone(&self.log_queue);
-        let flush_semaphore_clone = Arc::clone(&self.flush_semaphore);
-        let thread_safe_self: Arc<RwLock<LoggerThread>> = Arc::new(self.into());
-        let thread_safe_self_clone = Arc::clone(&thread_safe_self);
+    #[allow(unused_variables, unused_mut)]
+    pub fn std_stream(mut self, std_stream: StdStreamPolicy) -> Self {
+        #[cfg(feature = "stdout")]
+        {
+            self.config.std_stream = std_stream;
+        }
+        self
+    }
 
-        #[cfg(feature = "tracing")]
+    #[allow(unused_variables, unused_mut)]
+    pub fn context_log_path(mut self, context_log_path: impl Into<PathBuf>) -> Self {
+        #[cfg(feature = "file")]
         {
-            use cfg_if::cfg_if;
-            cfg_if! {
-                if #[cfg(all(target_os = "android"))] {
-                    paranoid_android::init("paper");
-                }
-            }
+            self.config.context_log_path = context_log_path.into();
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn rotation(mut self, rotation: RotationPolicy) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.rotation = rotation;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.max_files = max_files;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn compress_rotated(mut self, compress_rotated: bool) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.compress_rotated = compress_rotated;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn compress_active(mut self, compress_active: bool) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.compress_active = compress_active;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn append(mut self, append: bool) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.append = append;
         }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn fallback_to_stdout_on_file_error(mut self, fallback_to_stdout_on_file_error: bool) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.fallback_to_stdout_on_file_error = fallback_to_stdout_on_file_error;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn write_buffer_capacity(mut self, write_buffer_capacity: usize) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.write_buffer_capacity = write_buffer_capacity;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn flush_every_line(mut self, flush_every_line: bool) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.flush_every_line = flush_every_line;
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn error_log_path(mut self, error_log_path: impl Into<PathBuf>) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.error_log_path = Some(error_log_path.into());
+        }
+        self
+    }
+
+    #[allow(unused_variables, unused_mut)]
+    pub fn error_log_level(mut self, error_log_level: LogLevel) -> Self {
+        #[cfg(feature = "file")]
+        {
+            self.config.error_log_level = error_log_level;
+        }
+        self
+    }
+
+    /// Fails if the config is invalid, see [`LoggerConfig::validate`]
+    pub fn build(self) -> Result<LoggerConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Tag of the logs of [`LoggerThread::backtrace`], so they can be filtered
+#[cfg(feature = "backtrace")]
+const BACKTRACE_TAG: &str = "BACKTRACE";
+
+/// How often [`LoggerThread::shutdown_timeout`] checks whether the writer thread finished
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often an idle writer thread checks for signals caught by [`install_signal_flush`]
+#[cfg(all(unix, feature = "signals"))]
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `LoggerThread::sink_in_progress` while the writer thread isn't calling a sink
+const NO_SINK_IN_PROGRESS: usize = usize::MAX;
+
+/// Clears the alive flag once the writer thread exits, including by panicking
+struct WriterAliveGuard(Arc<AtomicBool>);
+
+impl Drop for WriterAliveGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Unregisters the signal handlers once the writer thread which acts on them is gone,
+/// so the signals get their default action again instead of being swallowed
+#[cfg(all(unix, feature = "signals"))]
+struct SignalHandlersGuard(Arc<Mutex<Vec<signal_hook::SigId>>>);
+
+#[cfg(all(unix, feature = "signals"))]
+impl Drop for SignalHandlersGuard {
+    fn drop(&mut self) {
+        for id in self.0.lock().unwrap_or_else(PoisonError::into_inner).drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+/// Lets [`LoggerThread::flush`] tell apart a flush that covers its logs from an earlier one
+#[derive(Debug, Default)]
+struct FlushState {
+    requested: AtomicU64,
+    completed: Mutex<u64>,
+    cond_var: Condvar,
+}
+
+impl FlushState {
+    fn complete(&self, request: u64) {
+        let mut completed = self.completed.lock().unwrap_or_else(PoisonError::into_inner);
+        *completed = (*completed).max(request);
+        self.cond_var.notify_all();
+    }
+}
+
+pub struct LoggerThread {
+    pub config: LoggerConfig,
+    // built from `config.log_format` unless replaced with `set_formatter`
+    formatter: Arc<dyn LogFormatter>,
+
+    log_queue: Arc<(SemaphoreLite, Mutex<VecDeque<LogData>>)>,
+    // notified with the queue lock held whenever the writer thread drains the queue
+    queue_space: Arc<Condvar>,
+    flush_semaphore: Arc<SemaphoreLite>,
+    flush_state: Arc<FlushState>,
+    // set under the queue lock when the writer thread drains logs, cleared once they are flushed
+    writer_busy: Arc<AtomicBool>,
+    dropped_logs: AtomicU64,
+    // indexed by severity
+    sampled_out_logs: [AtomicU64; 4],
+    rate_limited_logs: AtomicU64,
+    // counted by the writer thread, like `logged`
+    expired_logs: AtomicU64,
+    // counted by the writer thread
+    logged: AtomicU64,
+    rate_limits: Mutex<HashMap<String, RateLimiter>>,
+    // lets logs skip the `rate_limits` lock while no tag is limited
+    has_rate_limits: bool,
+    // tags of logs which are never filtered or dropped, see `set_guaranteed_tag`
+    guaranteed_tags: HashSet<String>,
+
+    inited: AtomicBool,
+    // master switch, see `set_enabled`
+    enabled: AtomicBool,
+    // last `LogData::seq` handed out
+    seq: AtomicU64,
+    // set once the warning about recovering a poisoned lock was queued
+    poison_recovered: AtomicBool,
+    // severity of the minimum level, so it can be changed without a write lock
+    min_level: AtomicU8,
+    // takes over from `config.max_string_len` for the same reason
+    max_string_len: AtomicUsize,
+
+    shutdown_flag: Arc<AtomicBool>,
+    writer_handle: Mutex<Option<JoinHandle<Result<()>>>>,
+    writer_alive: Arc<AtomicBool>,
+    // number of the signal `install_signal_flush` caught, 0 until then
+    #[cfg(all(unix, feature = "signals"))]
+    pending_signal: Arc<AtomicUsize>,
+    // set by the handler `install_reopen_on_sighup` installs
+    #[cfg(all(unix, feature = "signals", feature = "file"))]
+    pending_reopen: Arc<AtomicBool>,
+    // the writer thread only polls for caught signals once handlers are installed
+    #[cfg(all(unix, feature = "signals"))]
+    signal_handlers_installed: AtomicBool,
+    // set by the first signal `install_signal_flush` caught, a second one terminates right away
+    #[cfg(all(unix, feature = "signals"))]
+    signal_caught: Arc<AtomicBool>,
+    // handlers of this logger, unregistered when the writer thread exits
+    #[cfg(all(unix, feature = "signals"))]
+    signal_ids: Arc<Mutex<Vec<signal_hook::SigId>>>,
+    last_writer_error: Arc<Mutex<Option<String>>>,
+    // lets `restart_writer` hand the new writer thread its own handle to the logger
+    self_handle: OnceLock<Weak<RwLock<LoggerThread>>>,
+
+    // writers are behind their own locks so the writer thread only ever needs
+    // read access to the logger
+    // `None` once file logging fell back to stdout, see `LoggerConfig::fallback_to_stdout_on_file_error`
+    #[cfg(feature = "file")]
+    global_output: Mutex<Option<GlobalOutput>>,
+    // see `LoggerConfig::error_log_path`
+    #[cfg(feature = "file")]
+    error_file: Mutex<Option<LogFile>>,
+
+    #[cfg(feature = "file")]
+    context_map: Mutex<HashMap<String, LogFile>>,
+    /// Layouts of contexts added with [`LoggerThread::add_context_with_formatter`],
+    /// the others use `formatter`
+    #[cfg(feature = "file")]
+    context_formatters: HashMap<String, Arc<dyn LogFormatter>>,
+
+    sinks: Vec<Box<dyn LogCallback>>,
+    named_sinks: HashMap<String, Box<dyn LogCallback>>,
+    formatted_sinks: Vec<Box<dyn FormattedLogCallback>>,
+    // index into `sinks`, `named_sinks` and `formatted_sinks` of the sink the writer thread is calling
+    sink_in_progress: AtomicUsize,
+    #[cfg(feature = "sinks")]
+    sink_failures: Mutex<sink_logger::SinkFailures>,
+    #[cfg(feature = "sinks")]
+    slow_sinks: Mutex<sink_logger::SlowSinks>,
+    last_sink_error: Mutex<Option<String>>,
+    #[cfg(feature = "sinks")]
+    async_sinks: Vec<AsyncSink>,
+    #[cfg(feature = "tcp")]
+    tcp_sinks: Vec<tcp_sink::TcpSink>,
+    ring_buffer: Option<RingBuffer>,
+    alert_hook: Option<(LogLevel, Box<dyn Fn(&LogData) + Send + Sync>)>,
+}
+
+impl LoggerThread {
+    pub fn new(config: LoggerConfig, log_path: PathBuf) -> Result<Self> {
+        let logger = Self::without_output(config)?;
+
+        #[cfg(all(feature = "file", not(feature = "disabled")))]
+        {
+            let files = open_global_file(&logger.config, &log_path).and_then(|global_file| {
+                let error_file = match &logger.config.error_log_path {
+                    Some(error_log_path) => Some(open_error_file(&logger.config, error_log_path)?),
+                    None => None,
+                };
+                Ok((global_file, error_file))
+            });
+            let (global_file, error_file) = match files {
+                Ok(files) => files,
+                Err(e) if logger.config.fallback_to_stdout_on_file_error => {
+                    // the logger isn't running yet, stderr is the only place left to report this
+                    eprintln!("paper2: file logging disabled, falling back to stdout: {e:?}");
+                    return Ok(logger);
+                }
+                Err(e) => return Err(e),
+            };
+            *logger.global_output.lock().unwrap() = Some(GlobalOutput::File(global_file));
+            *logger.error_file.lock().unwrap() = error_file;
+        }
+
+        Ok(logger)
+    }
+
+    /// Like [`LoggerThread::new`], but the lines of the global file go to `writer` instead,
+    /// e.g. a `Vec<u8>` or a pipe. No directory or file is created for it.
+    /// Contexts have no files either, as they would live next to the global file
+    #[cfg(feature = "file")]
+    pub fn with_writer(config: LoggerConfig, writer: impl Write + Send + 'static) -> Result<Self> {
+        let logger = Self::without_output(config)?;
+
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let writer = std::io::BufWriter::with_capacity(logger.config.write_buffer_capacity, writer);
+        *logger.global_output.lock().unwrap() = Some(GlobalOutput::Writer(writer));
+
+        Ok(logger)
+    }
+
+    /// A logger with file logging fallen back to stdout until a global output is set
+    fn without_output(config: LoggerConfig) -> Result<Self> {
+        config.validate()?;
+
+        let log_queue = Arc::new((SemaphoreLite::new(), Mutex::new(VecDeque::with_capacity(config.log_max_buffer_count))));
+        let queue_space = Arc::new(Condvar::new());
+        let flush_semaphore = Arc::new(SemaphoreLite::new());
+
+        Ok(LoggerThread {
+            min_level: AtomicU8::new(config.min_level.as_u8()),
+            max_string_len: AtomicUsize::new(config.max_string_len),
+            formatter: config.log_format.formatter(&config),
+            config,
+            log_queue,
+            queue_space,
+            flush_semaphore,
+            flush_state: Arc::new(FlushState::default()),
+            writer_busy: Arc::new(AtomicBool::new(false)),
+            dropped_logs: AtomicU64::new(0),
+            sampled_out_logs: Default::default(),
+            rate_limited_logs: AtomicU64::new(0),
+            expired_logs: AtomicU64::new(0),
+            logged: AtomicU64::new(0),
+            rate_limits: Mutex::new(HashMap::new()),
+            has_rate_limits: false,
+            guaranteed_tags: HashSet::new(),
+            inited: AtomicBool::new(false),
+            enabled: AtomicBool::new(!cfg!(feature = "disabled")),
+            seq: AtomicU64::new(0),
+            poison_recovered: AtomicBool::new(false),
+
+            shutdown_flag: Arc::new(AtomicBool::new(false)),
+            writer_handle: Mutex::new(None),
+            writer_alive: Arc::new(AtomicBool::new(false)),
+            #[cfg(all(unix, feature = "signals"))]
+            pending_signal: Arc::new(AtomicUsize::new(0)),
+            #[cfg(all(unix, feature = "signals", feature = "file"))]
+            pending_reopen: Arc::new(AtomicBool::new(false)),
+            #[cfg(all(unix, feature = "signals"))]
+            signal_handlers_installed: AtomicBool::new(false),
+            #[cfg(all(unix, feature = "signals"))]
+            signal_caught: Arc::new(AtomicBool::new(false)),
+            #[cfg(all(unix, feature = "signals"))]
+            signal_ids: Arc::new(Mutex::new(Vec::new())),
+            last_writer_error: Arc::new(Mutex::new(None)),
+            self_handle: OnceLock::new(),
+
+            #[cfg(feature = "file")]
+            global_output: Mutex::new(None),
+            #[cfg(feature = "file")]
+            error_file: Mutex::new(None),
+
+            #[cfg(feature = "file")]
+            context_map: Mutex::new(HashMap::new()),
+            #[cfg(feature = "file")]
+            context_formatters: HashMap::new(),
+
+            sinks: Vec::new(),
+            named_sinks: HashMap::new(),
+            formatted_sinks: Vec::new(),
+            sink_in_progress: AtomicUsize::new(NO_SINK_IN_PROGRESS),
+            #[cfg(feature = "sinks")]
+            sink_failures: Mutex::new(sink_logger::SinkFailures::default()),
+            #[cfg(feature = "sinks")]
+            slow_sinks: Mutex::new(sink_logger::SlowSinks::default()),
+            last_sink_error: Mutex::new(None),
+            #[cfg(feature = "sinks")]
+            async_sinks: Vec::new(),
+            #[cfg(feature = "tcp")]
+            tcp_sinks: Vec::new(),
+            ring_buffer: None,
+            alert_hook: None,
+        })
+    }
+
+    /// Spawns the writer thread. Every instance has its own, so several loggers with their own
+    /// files, levels and sinks can run side by side, as long as their files don't overlap.
+    ///
+    /// With `install_panic_hook`, panics are written to this logger by [`panic_hook`]. The hook
+    /// installed before still runs afterwards, so every logger initialized this way records panics
+    pub fn init(self, install_panic_hook: bool) -> Result<ThreadSafeLoggerThread> {
+        if self.inited.load(Ordering::SeqCst) {
+            bail!("LoggerThread already initialized");
+        }
+
+        self.inited.store(true, Ordering::SeqCst);
+
+        // ahead of anything queued before init
+        if self.config.log_startup_banner {
+            let banner = self.startup_banner();
+            let mut queue = self.log_queue.1.lock().unwrap_or_else(PoisonError::into_inner);
+            queue.push_front(banner);
+        }
+
+        let thread_safe_self: Arc<RwLock<LoggerThread>> = Arc::new(self.into());
+
+        #[cfg(feature = "tracing")]
+        {
+            use cfg_if::cfg_if;
+            cfg_if! {
+                if #[cfg(all(target_os = "android"))] {
+                    // the subscriber is global and may only be set once, not once per instance
+                    static ANDROID_TRACING: std::sync::Once = std::sync::Once::new();
+                    ANDROID_TRACING.call_once(|| paranoid_android::init("paper"));
+                }
+            }
+        }
+
+        if install_panic_hook {
+            let previous_hook = std::panic::take_hook();
+            let hook = panic_hook(true, true, thread_safe_self.clone());
+            std::panic::set_hook(Box::new(move |info| {
+                hook(info);
+                previous_hook(info);
+            }));
+        }
+
+        {
+            let logger_thread = read_logger(&thread_safe_self);
+            let _ = logger_thread.self_handle.set(Arc::downgrade(&thread_safe_self));
+            // nothing would ever be queued for it
+            if !cfg!(feature = "disabled") {
+                logger_thread.spawn_writer(Arc::clone(&thread_safe_self));
+            }
+        }
+
+        Ok(thread_safe_self)
+    }
+
+    /// `paper2 0.1.0 started` with the settings deciding which logs end up where as fields
+    fn startup_banner(&self) -> LogData {
+        let features = [
+            ("file", cfg!(feature = "file")),
+            ("stdout", cfg!(feature = "stdout")),
+            ("sinks", cfg!(feature = "sinks")),
+            ("logcat", cfg!(feature = "logcat")),
+            ("tracing", cfg!(feature = "tracing")),
+            ("ffi", cfg!(feature = "ffi")),
+            ("backtrace", cfg!(feature = "backtrace")),
+            ("graphemes", cfg!(feature = "graphemes")),
+            ("log-compat", cfg!(feature = "log-compat")),
+            ("compression", cfg!(feature = "compression")),
+            ("syslog", cfg!(feature = "syslog")),
+            ("tcp", cfg!(feature = "tcp")),
+            ("hostname", cfg!(feature = "hostname")),
+            ("signals", cfg!(feature = "signals")),
+            ("wineventlog", cfg!(feature = "wineventlog")),
+        ]
+        .into_iter()
+        .filter_map(|(feature, enabled)| enabled.then_some(feature))
+        .join(",");
+
+        #[allow(unused_mut)]
+        let mut fields = vec![
+            ("min_level".to_string(), self.min_level().to_string()),
+            ("format".to_string(), format!("{:?}", self.config.log_format)),
+        ];
+
+        #[cfg(feature = "file")]
+        {
+            let log_path = match &*self.global_output.lock().unwrap_or_else(PoisonError::into_inner) {
+                Some(GlobalOutput::File(global_file)) => global_file.path().display().to_string(),
+                Some(GlobalOutput::Writer(_)) => "writer".to_string(),
+                None => "disabled".to_string(),
+            };
+            fields.extend([
+                ("log_path".to_string(), log_path),
+                ("rotation".to_string(), format!("{:?}", self.config.rotation)),
+                ("max_files".to_string(), self.config.max_files.to_string()),
+            ]);
+        }
+
+        fields.push(("features".to_string(), features));
+
+        LogData {
+            level: LogLevel::Info,
+            message: format!("paper2 {} started", env!("CARGO_PKG_VERSION")),
+            file: file!().into(),
+            line: line!(),
+            column: column!(),
+            fields,
+            ..Default::default()
+        }
+    }
+
+    fn spawn_writer(&self, thread_safe_self: ThreadSafeLoggerThread) {
+        let log_queue_clone = Arc::clone(&self.log_queue);
+        let queue_space_clone = Arc::clone(&self.queue_space);
+        let flush_semaphore_clone = Arc::clone(&self.flush_semaphore);
+        let flush_state_clone = Arc::clone(&self.flush_state);
+        let writer_busy_clone = Arc::clone(&self.writer_busy);
+        let shutdown_flag_clone = Arc::clone(&self.shutdown_flag);
+        let last_writer_error_clone = Arc::clone(&self.last_writer_error);
+
+        self.writer_alive.store(true, Ordering::SeqCst);
+        // a writer thread which died mid-batch never got to clear it
+        self.writer_busy.store(false, Ordering::SeqCst);
+        let alive_guard = WriterAliveGuard(Arc::clone(&self.writer_alive));
+        #[cfg(all(unix, feature = "signals"))]
+        let signal_handlers_guard = SignalHandlersGuard(Arc::clone(&self.signal_ids));
+
+        let handle = thread::spawn(move || {
+            // declared first so it drops last, after the writer is marked dead
+            #[cfg(all(unix, feature = "signals"))]
+            let _signal_handlers_guard = signal_handlers_guard;
+            let _alive_guard = alive_guard;
+
+            let result = Self::log_thread(
+                log_queue_clone,
+                queue_space_clone,
+                flush_semaphore_clone,
+                flush_state_clone,
+                writer_busy_clone,
+                shutdown_flag_clone,
+                thread_safe_self,
+            );
+            if let Err(report) = &result {
+                *last_writer_error_clone.lock().unwrap_or_else(PoisonError::into_inner) = Some(format!("{report:?}"));
+            }
+
+            result
+        });
+        *self.writer_handle.lock().unwrap() = Some(handle);
+    }
+
+    /// Whether the writer thread is running. It stops after [`LoggerThread::shutdown`]
+    /// or when writing a log fails, see [`LoggerThread::last_writer_error`]
+    pub fn is_writer_alive(&self) -> bool {
+        self.writer_alive.load(Ordering::SeqCst)
+    }
+
+    /// The failures of the sinks for the most recent log some sink failed for, if any.
+    /// Unlike other errors these don't stop the writer thread, see `LoggerConfig::sink_failure_limit`
+    pub fn last_sink_error(&self) -> Option<String> {
+        self.last_sink_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The error which stopped the writer thread most recently, if any
+    pub fn last_writer_error(&self) -> Option<String> {
+        self.last_writer_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    ///
+    /// Respawns the writer thread if it died. Returns `false` if it was still running.
+    /// Fails if the logger was never initialized or has been shut down.
+    pub fn restart_writer(&self) -> Result<bool> {
+        if self.is_shutdown() {
+            bail!("LoggerThread has been shut down");
+        }
+        if cfg!(feature = "disabled") {
+            return Ok(false);
+        }
+
+        let Some(thread_safe_self) = self.self_handle.get().and_then(Weak::upgrade) else {
+            bail!("LoggerThread is not initialized");
+        };
+
+        if self.is_writer_alive() {
+            return Ok(false);
+        }
+
+        // the old thread has finished, reap it and note if it panicked
+        if let Some(handle) = self.writer_handle.lock().unwrap().take() {
+            if handle.join().is_err() {
+                *self.last_writer_error.lock().unwrap_or_else(PoisonError::into_inner) =
+                    Some("Logger thread panicked".to_string());
+            }
+        }
+
+        self.spawn_writer(thread_safe_self);
+
+        Ok(true)
+    }
+
+    ///
+    /// Signals the writer thread to drain the remaining queue, flush every file and exit.
+    /// Blocks until the writer thread has been joined, so all logs queued before this call
+    /// are on disk once it returns.
+    ///
+    /// The writer thread holds its own handle to the logger, so dropping the last
+    /// [`ThreadSafeLoggerThread`] does not stop it. Call this before exiting instead.
+    pub fn shutdown(&self) -> Result<()> {
+        let Some(handle) = self.begin_shutdown() else {
+            return Ok(());
+        };
+
+        handle
+            .join()
+            .map_err(|_| eyre!("Logger thread panicked during shutdown"))?
+    }
+
+    /// [`LoggerThread::shutdown`] which gives up on the writer thread after `timeout`, so a hanging
+    /// sink can't hang the app's exit with it. The writer thread is then left running detached
+    /// and the logs it hasn't written yet are lost. That is an error naming the sink the writer
+    /// thread was stuck in, if it was in one
+    pub fn shutdown_timeout(&self, timeout: Duration) -> Result<()> {
+        let Some(handle) = self.begin_shutdown() else {
+            return Ok(());
+        };
+
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            let now = Instant::now();
+            if now >= deadline {
+                let stuck_in = match self.sink_in_progress() {
+                    Some(sink) => format!(" while calling {sink}"),
+                    None => String::new(),
+                };
+                // dropping the handle detaches the thread
+                bail!("Logger thread didn't finish within {timeout:?}{stuck_in}, unwritten logs are lost");
+            }
+            thread::sleep(SHUTDOWN_POLL_INTERVAL.min(deadline - now));
+        }
+
+        handle
+            .join()
+            .map_err(|_| eyre!("Logger thread panicked during shutdown"))?
+    }
+
+    /// Tells the writer thread to write what's queued and stop, returning it if it's running
+    fn begin_shutdown(&self) -> Option<JoinHandle<Result<()>>> {
+        self.shutdown_flag.store(true, Ordering::SeqCst);
+        self.log_queue.0.signal();
+
+        // release producers blocked on a full queue, the writer thread won't make room anymore
+        {
+            let _queue = self.log_queue.1.lock().unwrap();
+            self.queue_space.notify_all();
+        }
+
+        self.writer_handle.lock().unwrap().take()
+    }
+
+    /// The sink the writer thread is calling right now, if any
+    fn sink_in_progress(&self) -> Option<String> {
+        let index = self.sink_in_progress.load(Ordering::Relaxed);
+        if index == NO_SINK_IN_PROGRESS {
+            return None;
+        }
+
+        let Some(named_index) = index.checked_sub(self.sinks.len()) else {
+            return Some(format!("sink #{index}"));
+        };
+        match named_index.checked_sub(self.named_sinks.len()) {
+            None => self
+                .named_sinks
+                .keys()
+                .nth(named_index)
+                .map(|name| format!("sink {name:?}")),
+            Some(formatted_index) => Some(format!("formatted sink #{formatted_index}")),
+        }
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown_flag.load(Ordering::SeqCst)
+    }
+
+    pub fn formatter(&self) -> &Arc<dyn LogFormatter> {
+        &self.formatter
+    }
+
+    /// Replaces the layout picked by `config.log_format` for the file and stdout loggers
+    pub fn set_formatter(&mut self, formatter: impl LogFormatter + 'static) {
+        self.formatter = Arc::new(formatter);
+    }
+
+    /// Writes `log` with the formatter the file and stdout loggers use
+    pub fn write_log(&self, log: &LogData, writer: &mut dyn Write) -> std::io::Result<()> {
+        self.formatter.format(log, writer)
+    }
+
+    pub fn is_inited(&self) -> &AtomicBool {
+        &self.inited
+    }
+
+    pub fn get_queue(&self) -> &Mutex<VecDeque<LogData>> {
+        &self.log_queue.1
+    }
+
+    pub fn get_sinks(&self) -> &Vec<Box<dyn LogCallback>> {
+        &self.sinks
+    }
+
+    pub fn min_level(&self) -> LogLevel {
+        LogLevel::from_u8(self.min_level.load(Ordering::Relaxed))
+    }
+
+    /// Changes the minimum level at runtime, logs below it are no longer queued
+    pub fn set_min_level(&self, level: LogLevel) {
+        self.min_level.store(level.as_u8(), Ordering::Relaxed);
+    }
+
+    pub fn max_string_len(&self) -> usize {
+        self.max_string_len.load(Ordering::Relaxed)
+    }
+
+    /// Changes the length lines are split or truncated at, from the next drain of the queue on
+    pub fn set_max_string_len(&self, max_string_len: usize) -> Result<()> {
+        if max_string_len == 0 {
+            bail!("max_string_len must be greater than 0");
+        }
+
+        self.max_string_len.store(max_string_len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Whether a log of this level would be queued.
+    /// Check this before building an expensive [`LogData`]
+    pub fn is_level_enabled(&self, level: LogLevel) -> bool {
+        self.enabled.load(Ordering::Relaxed) && level.as_u8() >= self.min_level.load(Ordering::Relaxed)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Switches all logging on or off at runtime. While off, [`LoggerThread::queue_log`] and
+    /// [`LoggerThread::log_sync`] return before allocating or locking anything, so nothing reaches
+    /// the files or sinks. Logs queued before switching off still reach every output.
+    /// With the `disabled` feature logging stays off
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled
+            .store(enabled && !cfg!(feature = "disabled"), Ordering::Relaxed);
+    }
+
+    /// Number of logs dropped so far because the queue was full
+    pub fn dropped_log_count(&self) -> u64 {
+        self.dropped_logs.load(Ordering::Relaxed)
+    }
+
+    /// Number of logs waiting for the writer thread
+    pub fn queue_depth(&self) -> usize {
+        self.log_queue.1.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    /// Number of logs the writer thread has written out, before splitting into lines
+    pub fn total_logged(&self) -> u64 {
+        self.logged.load(Ordering::Relaxed)
+    }
+
+    /// Number of logs which passed the minimum level but were never queued,
+    /// because of a full queue, sampling or rate limiting
+    pub fn total_dropped(&self) -> u64 {
+        let sampled_out: u64 = self
+            .sampled_out_logs
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum();
+
+        self.dropped_logs.load(Ordering::Relaxed) + sampled_out + self.rate_limited_logs.load(Ordering::Relaxed)
+    }
+
+    /// Number of queued logs the writer thread dropped for waiting longer than `config.max_queue_age`.
+    /// Unlike [`LoggerThread::total_dropped`], these made it into the queue
+    pub fn expired_log_count(&self) -> u64 {
+        self.expired_logs.load(Ordering::Relaxed)
+    }
+
+    /// Number of logs of this level skipped because of `config.sample_rate`
+    pub fn sampled_out_count(&self, level: LogLevel) -> u64 {
+        self.sampled_out_logs[level.as_u8() as usize].load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Limits logs with this tag to `max_per_sec`, allowing bursts of up to that many.
+    /// Logs over the budget are dropped, and a summary of how many were suppressed
+    /// is queued before the next allowed log of the tag.
+    pub fn set_rate_limit(&mut self, tag: &str, max_per_sec: u32) {
+        self.rate_limits
+            .get_mut()
+            .unwrap()
+            .insert(tag.to_string(), RateLimiter::new(max_per_sec));
+        self.has_rate_limits = true;
+    }
+
+    /// Returns whether the tag had a rate limit
+    pub fn remove_rate_limit(&mut self, tag: &str) -> bool {
+        let rate_limits = self.rate_limits.get_mut().unwrap();
+        let removed = rate_limits.remove(tag).is_some();
+        self.has_rate_limits = !rate_limits.is_empty();
+        removed
+    }
+
+    ///
+    /// Guarantees delivery of logs with this tag, e.g. `audit` records. They skip `min_level`,
+    /// sampling and rate limits, are queued past `log_max_buffer_count` whatever the
+    /// `overflow_policy`, are never collapsed by `dedup` and have the files flushed right after
+    /// they are written. Only switching logging off with [`LoggerThread::set_enabled`] stops them.
+    /// Can be called for several tags
+    pub fn set_guaranteed_tag(&mut self, tag: &str) {
+        self.guaranteed_tags.insert(tag.to_string());
+    }
+
+    /// Returns whether the tag was guaranteed delivery
+    pub fn remove_guaranteed_tag(&mut self, tag: &str) -> bool {
+        self.guaranteed_tags.remove(tag)
+    }
+
+    fn is_guaranteed(&self, log: &LogData) -> bool {
+        !self.guaranteed_tags.is_empty() && log.tag.as_deref().is_some_and(|tag| self.guaranteed_tags.contains(tag))
+    }
+
+    ///
+    /// Pushes the log onto the queue for the writer thread, unless it is below the
+    /// minimum level, sampled out or over the rate limit of its tag.
+    ///
+    /// The calling thread is recorded on the log, unless `thread_id` is already set.
+    ///
+    /// Poisoned locks are recovered rather than unwrapped, so a panic elsewhere
+    /// never escalates into the thread that is logging.
+    pub fn queue_log(&self, mut log_data: LogData) -> Result<()> {
+        if self.is_guaranteed(&log_data) {
+            if self.is_enabled() {
+                log_data.capture_thread();
+                self.push_log(log_data);
+            }
+            return Ok(());
+        }
+        if !self.is_level_enabled(log_data.level) {
+            return Ok(());
+        }
+        if let Some(&rate) = self.config.sample_rate.get(&log_data.level) {
+            if !sampling::keep(rate) {
+                self.sampled_out_logs[log_data.level.as_u8() as usize].fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+        log_data.capture_thread();
+
+        if let Some(tag) = log_data.tag.as_ref().filter(|_| self.has_rate_limits) {
+            let decision = match self
+                .rate_limits
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_mut(&**tag)
+            {
+                Some(rate_limiter) => rate_limiter.check(),
+                None => RateLimitDecision::Allow,
+            };
+
+            match decision {
+                RateLimitDecision::Allow => {}
+                RateLimitDecision::AllowAfterSuppressed(suppressed) => self.push_log(LogData {
+                    level: LogLevel::Warn,
+                    tag: Some(tag.clone()),
+                    message: format!("Rate limit suppressed {suppressed} logs"),
+                    file: file!().into(),
+                    line: line!(),
+                    column: column!(),
+                    ..Default::default()
+                }),
+                RateLimitDecision::Suppress => {
+                    self.rate_limited_logs.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+
+        self.push_log(log_data);
+        Ok(())
+    }
+
+    ///
+    /// Writes `log_data` to the global file and stdout right away and flushes the file, bypassing
+    /// the queue. Meant for last words before an abort, which the writer thread might not get
+    /// to drain in time. The global file's lock keeps the line from interleaving with the
+    /// writer thread's batches, logs still waiting in the queue end up after it.
+    ///
+    /// Only the level filter applies, the message isn't sampled, rate limited, split or sanitized
+    /// and contexts and sinks don't see it.
+    pub fn log_sync(&self, mut log_data: LogData) -> Result<()> {
+        if !self.is_level_enabled(log_data.level) {
+            return Ok(());
+        }
+        log_data.capture_thread();
+        log_data.seq = self.next_seq();
+        self.push_ring_buffer(&log_data);
+
+        #[cfg(feature = "file")]
+        {
+            let mut line = Vec::new();
+            self.formatter.format(&log_data, &mut line)?;
+            if !line.is_empty() {
+                self.config.line_ending.terminate(&mut line);
+
+                match &mut *self.global_output.lock().unwrap_or_else(PoisonError::into_inner) {
+                    Some(global_output) => {
+                        global_output.write_all(&line)?;
+                        global_output.flush()?;
+                    }
+                    None => file_logger::write_fallback(&line)?,
+                }
+
+                if log_data.level >= self.config.error_log_level {
+                    if let Some(error_file) = self.error_file.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+                        error_file.write_all(&line)?;
+                        error_file.flush()?;
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "stdout")]
+        stdout_logger::write_log(&log_data, self)?;
+
+        Ok(())
+    }
+
+    /// [`LoggerThread::queue_log`] with structured `key=value` fields attached
+    pub fn queue_log_with_fields(
+        &self,
+        mut log_data: LogData,
+        fields: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Result<()> {
+        log_data
+            .fields
+            .extend(fields.into_iter().map(|(key, value)| (key.into(), value.into())));
+
+        self.queue_log(log_data)
+    }
+
+    /// Queues `report` as a single error log, its message followed by every cause of its chain
+    /// on a line of its own. The lines are split like any multi-line message, so a long cause
+    /// is chunked rather than cut off
+    #[track_caller]
+    pub fn log_error_report(&self, report: &color_eyre::Report, tag: Option<&str>) -> Result<()> {
+        let location = std::panic::Location::caller();
+        let line_end = self.config.line_end;
+
+        let mut message = String::new();
+        for (depth, cause) in report.chain().enumerate() {
+            if depth > 0 {
+                message.push(line_end);
+                message.push_str("  caused by: ");
+            }
+            // a cause spanning several lines stays indented under its `caused by`
+            let cause = cause.to_string();
+            let mut lines = cause.split(line_end);
+            message.push_str(lines.next().unwrap_or_default());
+            for line in lines {
+                message.push(line_end);
+                message.push_str("    ");
+                message.push_str(line);
+            }
+        }
+
+        self.queue_log(LogData {
+            level: LogLevel::Error,
+            tag: tag.map(|tag| tag.to_string().into()),
+            message,
+            file: location.file().into(),
+            line: location.line(),
+            column: location.column(),
+            ..Default::default()
+        })
+    }
+
+    ///
+    /// Once the queue holds `log_max_buffer_count` logs, `overflow_policy` decides
+    /// whether this blocks or drops a log. Blocking is skipped when no writer thread
+    /// is running, since nothing would ever make room.
+    fn push_log(&self, mut log_data: LogData) {
+        // before a full queue gets to drop it, so the drop leaves a gap
+        log_data.seq = self.next_seq();
+        if self.config.track_write_latency || self.config.max_queue_age.is_some() {
+            log_data.enqueued_at = Some(Utc::now());
+        }
+
+        let (sempahore, queue) = self.log_queue.as_ref();
+        let max_count = self.config.log_max_buffer_count;
+
+        let mut queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+        // guaranteed logs neither wait for room nor get dropped
+        if max_count > 0 && queue.len() >= max_count && !self.is_guaranteed(&log_data) {
+            match self.config.overflow_policy {
+                OverflowPolicy::Block => {
+                    if self.inited.load(Ordering::SeqCst) && !self.is_shutdown() {
+                        sempahore.signal();
+                        queue = self
+                            .queue_space
+                            .wait_while(queue, |queue| queue.len() >= max_count && !self.is_shutdown())
+                            .unwrap_or_else(PoisonError::into_inner);
+                    }
+                }
+                OverflowPolicy::DropOldest => {
+                    self.dropped_logs.fetch_add(1, Ordering::Relaxed);
+                    match queue.iter().position(|log| !self.is_guaranteed(log)) {
+                        // usually the front, which a `VecDeque` drops without shifting the rest
+                        Some(oldest) => {
+                            queue.remove(oldest);
+                        }
+                        // only guaranteed logs are queued, this one has to go instead
+                        None => return,
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped_logs.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+
+        self.push_ring_buffer(&log_data);
+        queue.push_back(log_data);
+        drop(queue);
+        sempahore.signal();
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Queues a warning the first time a poisoned lock on the logger is recovered
+    fn warn_poison_recovered(&self) {
+        if self.poison_recovered.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        self.queue_internal_log(
+            LogLevel::Warn,
+            "Recovered the logger from a poisoned lock, a thread panicked while holding it".to_string(),
+        );
+    }
+
+    /// Queues a log about the logger itself.
+    /// Skips the queue limit, this may run on the writer thread which would block on itself
+    #[track_caller]
+    fn queue_internal_log(&self, level: LogLevel, message: String) {
+        let location = std::panic::Location::caller();
+        let log_data = LogData {
+            level,
+            message,
+            seq: self.next_seq(),
+            file: location.file().into(),
+            line: location.line(),
+            column: location.column(),
+            ..Default::default()
+        };
+        self.push_ring_buffer(&log_data);
+
+        let (sempahore, queue) = self.log_queue.as_ref();
+        queue.lock().unwrap_or_else(PoisonError::into_inner).push_back(log_data);
+        sempahore.signal();
+    }
+
+    /// Keeps `log` in the ring buffer sink, if there is one. Done when a log is accepted rather
+    /// than written, so a crash dump also has the logs still waiting in the queue
+    fn push_ring_buffer(&self, log: &LogData) {
+        if let Some(ring_buffer) = &self.ring_buffer {
+            ring_buffer.push(log);
+        }
+    }
+
+    /// Logs the current backtrace as an error tagged `BACKTRACE`. Like [`Backtrace::capture`],
+    /// this only captures if `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` asks for it
+    #[cfg(feature = "backtrace")]
+    #[inline(always)]
+    pub fn backtrace(&self) -> Result<()> {
+        self.queue_backtrace(Backtrace::capture(), LogLevel::Error)
+    }
+
+    /// [`LoggerThread::backtrace`] which always captures, whatever the environment says,
+    /// and logs at `level`
+    #[cfg(feature = "backtrace")]
+    #[inline(always)]
+    pub fn force_backtrace(&self, level: LogLevel) -> Result<()> {
+        self.queue_backtrace(Backtrace::force_capture(), level)
+    }
+
+    #[cfg(feature = "backtrace")]
+    fn queue_backtrace(&self, backtrace: Backtrace, level: LogLevel) -> Result<()> {
+        self.queue_log(LogData {
+            level,
+            tag: Some(BACKTRACE_TAG.into()),
+            message: format!("{:?}", backtrace),
+            file: file!().into(),
+            line: line!(),
+            column: column!(),
+            function_name: None,
+            ..LogData::default()
+        })
+    }
+
+    /// Creates `{context_log_path}/{tag}.log`, which receives every log queued with this tag
+    /// on top of the global file. Does nothing if file logging fell back to stdout
+    /// or the logger was created with [`LoggerThread::with_writer`].
+    /// Fails if `tag` is registered already, rather than truncating its file
+    #[cfg(feature = "file")]
+    pub fn add_context(&mut self, tag: &str) -> Result<()> {
+        if !matches!(self.global_output_mut(), Some(GlobalOutput::File(_))) {
+            return Ok(());
+        }
+        if self.context_map_mut().contains_key(tag) {
+            bail!("Context {tag} is already registered");
+        }
+
+        let log_path = self.config.context_log_path.join(tag).with_extension("log");
+        let file = LogFile::open(&log_path, &self.config)
+            .map_err(|e| eyre!("Unable to create context file at {}", e.to_string()))?;
+
+        self.context_map_mut().insert(tag.to_string(), file);
+        self.context_formatters.remove(tag);
+
+        Ok(())
+    }
+
+    /// [`LoggerThread::add_context`] which leaves a registered context and its file alone
+    /// instead of failing. Returns `false` if `tag` was registered already
+    #[cfg(feature = "file")]
+    pub fn add_or_get_context(&mut self, tag: &str) -> Result<bool> {
+        if self.context_map_mut().contains_key(tag) {
+            return Ok(false);
+        }
+
+        self.add_context(tag)?;
+        Ok(true)
+    }
+
+    /// [`LoggerThread::add_context`] whose file is written with `formatter` instead of the
+    /// one used for the global file, e.g. JSON for a context read by another tool
+    #[cfg(feature = "file")]
+    pub fn add_context_with_formatter(&mut self, tag: &str, formatter: Arc<dyn LogFormatter>) -> Result<()> {
+        self.add_context(tag)?;
+        self.context_formatters.insert(tag.to_string(), formatter);
+
+        Ok(())
+    }
+
+    /// Flushes and closes the file of a context registered with [`LoggerThread::add_context`]
+    #[cfg(feature = "file")]
+    pub fn remove_context(&mut self, tag: &str) -> Result<()> {
+        let file = self
+            .context_map_mut()
+            .remove(tag)
+            .ok_or_else(|| eyre!("Context {tag} is not registered"))?;
+        self.context_formatters.remove(tag);
+
+        file.close()
+            .with_context(|| format!("Unable to close context file for {tag}"))
+    }
+
+    /// Flushes and reopens the global file, the error file and every context file at their
+    /// configured paths, appending to whatever is there now. Call it once an external tool like
+    /// `logrotate` renamed them, otherwise logs keep going to the renamed files.
+    /// Output which isn't a file is left alone
+    #[cfg(feature = "file")]
+    pub fn reopen_files(&self) -> Result<()> {
+        // same order as the writer thread takes them
+        if let Some(GlobalOutput::File(global_file)) = self
+            .global_output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            global_file
+                .reopen_at_path()
+                .with_context(|| format!("Unable to reopen global file at {}", global_file.path().display()))?;
+        }
+        for (tag, file) in self
+            .context_map
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter_mut()
+        {
+            file.reopen_at_path()
+                .with_context(|| format!("Unable to reopen context file for {tag}"))?;
+        }
+        if let Some(error_file) = self.error_file.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            error_file
+                .reopen_at_path()
+                .with_context(|| format!("Unable to reopen error file at {}", error_file.path().display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Tags of the currently registered contexts, sorted
+    #[cfg(feature = "file")]
+    pub fn contexts(&self) -> Vec<String> {
+        let context_map = self.context_map.lock().unwrap_or_else(|poisoned| {
+            self.warn_poison_recovered();
+            poisoned.into_inner()
+        });
+        context_map.keys().cloned().sorted().collect()
+    }
+
+    /// The writer thread holds this while running formatters, so a panicking one poisons it
+    #[cfg(feature = "file")]
+    fn global_output_mut(&mut self) -> &mut Option<GlobalOutput> {
+        if self.global_output.is_poisoned() {
+            self.warn_poison_recovered();
+        }
+        self.global_output.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// [`LoggerThread::global_output_mut`] for the context files
+    #[cfg(feature = "file")]
+    fn context_map_mut(&mut self) -> &mut HashMap<String, LogFile> {
+        if self.context_map.is_poisoned() {
+            self.warn_poison_recovered();
+        }
+        self.context_map.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn add_sink<F>(&mut self, sink: F)
+    where
+        F: LogCallback + 'static,
+    {
+        #[cfg(feature = "sinks")]
+        {
+            self.sink_failures.get_mut().unwrap().reset_indexed();
+            self.slow_sinks.get_mut().unwrap().reset_indexed();
+        }
+        self.sinks.push(Box::new(sink));
+    }
 
-        if install_panic_hook {
-            std::panic::set_hook(panic_hook(true, true, thread_safe_self.clone()));
-        }
+    /// [`LoggerThread::add_sink`] which is only called for logs at or above `min_level`
+    pub fn add_sink_with_level<F>(&mut self, sink: F, min_level: LogLevel)
+    where
+        F: LogCallback + 'static,
+    {
+        self.add_sink(with_min_level(sink, min_level));
+    }
 
-        thread::spawn(move || {
-            Self::log_thread(
-                log_queue_clone,
-                flush_semaphore_clone,
-                thread_safe_self_clone,
-            )
-        });
+    ///
+    /// Adds a sink which runs on its own worker thread, so it can't hold up the file
+    /// and stdout output. Logs are dropped for this sink once it falls
+    /// `async_sink_buffer` logs behind, see [`LoggerThread::async_sink_dropped_count`].
+    /// Logs the sink returns [`SinkStatus::Retry`] for are retried with backoff,
+    /// logs it drops or returns an error for are counted as dropped as well.
+    /// Requires the `sinks` feature
+    #[cfg(feature = "sinks")]
+    pub fn add_async_sink<F>(&mut self, sink: F) -> Result<()>
+    where
+        F: LogSink + 'static,
+    {
+        self.add_async_sink_with_level(sink, LogLevel::Debug)
+    }
 
-        Ok(thread_safe_self)
+    /// [`LoggerThread::add_async_sink`] which only receives logs at or above `min_level`
+    #[cfg(feature = "sinks")]
+    pub fn add_async_sink_with_level<F>(&mut self, sink: F, min_level: LogLevel) -> Result<()>
+    where
+        F: LogSink + 'static,
+    {
+        let async_sink = AsyncSink::spawn(sink, self.config.async_sink_buffer, min_level)
+            .context("Unable to spawn async sink worker")?;
+        self.async_sinks.push(async_sink);
+
+        Ok(())
     }
 
-    pub fn is_inited(&self) -> &AtomicBool {
-        &self.inited
+    ///
+    /// Forwards every log to a syslog server as RFC 5424 messages.
+    /// Runs as an async sink, so a slow or unreachable server never blocks the writer thread.
+    #[cfg(feature = "syslog")]
+    pub fn add_syslog_sink(
+        &mut self,
+        addr: impl std::net::ToSocketAddrs,
+        transport: syslog_sink::SyslogTransport,
+    ) -> Result<()> {
+        let syslog_sink = syslog_sink::SyslogSink::new(addr, transport)?;
+
+        self.add_async_sink(move |log: &LogData| syslog_sink.send(log))
     }
 
-    pub fn get_queue(&self) -> &Mutex<Vec<LogData>> {
-        &self.log_queue.1
+    ///
+    /// Reports every log at or above `Info` to the Windows Event Log under `source`,
+    /// errors as Error events, warnings as Warning events and the rest as Information.
+    /// Runs as an async sink, so a slow event log service never blocks the writer thread.
+    #[cfg(all(windows, feature = "wineventlog"))]
+    pub fn add_eventlog_sink(&mut self, source: &str) -> Result<()> {
+        self.add_eventlog_sink_with_level(source, LogLevel::Info)
     }
 
-    pub fn get_sinks(&self) -> &Vec<Box<dyn LogCallback>> {
-        &self.sinks
+    /// [`LoggerThread::add_eventlog_sink`] which only reports logs at or above `min_level`
+    #[cfg(all(windows, feature = "wineventlog"))]
+    pub fn add_eventlog_sink_with_level(&mut self, source: &str, min_level: LogLevel) -> Result<()> {
+        let eventlog_sink = eventlog_sink::EventLogSink::new(source)?;
+
+        self.add_async_sink_with_level(move |log: &LogData| eventlog_sink.send(log), min_level)
     }
 
-    pub fn queue_log(&self, log_data: LogData) {
-        let (sempahore, queue) = self.log_queue.as_ref();
+    ///
+    /// Writes every log to `addr` as a line formatted like the file output, for aggregators
+    /// accepting newline-delimited text over TCP. Runs on its own thread and reconnects with
+    /// exponential backoff. Up to `async_sink_buffer` lines are kept while disconnected,
+    /// see [`LoggerThread::tcp_sink_dropped_count`] for the rest.
+    #[cfg(feature = "tcp")]
+    pub fn add_tcp_sink(&mut self, addr: std::net::SocketAddr) -> Result<()> {
+        let tcp_sink =
+            tcp_sink::TcpSink::spawn(addr, self.config.async_sink_buffer).context("Unable to spawn tcp sink worker")?;
+        self.tcp_sinks.push(tcp_sink);
 
-        queue.lock().unwrap().push(log_data);
-        sempahore.signal();
+        Ok(())
     }
 
-    #[cfg(feature = "backtrace")]
-    #[inline(always)]
-    pub fn backtrace(&self) -> Result<()> {
-        use std::backtrace::Backtrace;
+    /// Number of lines dropped across all tcp sinks, because they were disconnected for too long
+    #[cfg(feature = "tcp")]
+    pub fn tcp_sink_dropped_count(&self) -> u64 {
+        self.tcp_sinks.iter().map(tcp_sink::TcpSink::dropped_count).sum()
+    }
 
-        let backtrace = Backtrace::capture();
-        let backtrace_str = format!("{:?}", backtrace);
+    ///
+    /// Keeps the last `capacity` logs in memory, including ones still queued for the writer thread,
+    /// so they can be attached to crash reports with [`LoggerThread::dump_ring_buffer`].
+    /// Logs are kept as they were queued, before splitting or deduplication.
+    /// Calling this again replaces the buffer
+    pub fn add_ring_buffer_sink(&mut self, capacity: usize) {
+        self.ring_buffer = Some(RingBuffer::new(capacity));
+    }
 
-        self.queue_log(LogData {
-            level: LogLevel::Error,
-            tag: None,
-            message: backtrace_str,
-            file: file!().into(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..LogData::default()
-        });
+    ///
+    /// Calls `hook` on the writer thread for every log at or above `threshold`, e.g. to page
+    /// someone on errors. Meant for rare, important logs, unlike the sinks it isn't called
+    /// for anything below the threshold. A split message calls it once, with the first chunk.
+    /// Calling this again replaces the hook.
+    pub fn set_alert_hook(&mut self, threshold: LogLevel, hook: impl Fn(&LogData) + Send + Sync + 'static) {
+        self.alert_hook = Some((threshold, Box::new(hook)));
+    }
 
-        Ok(())
+    /// The logs held by the ring buffer sink, oldest first. Empty without one
+    pub fn dump_ring_buffer(&self) -> Vec<LogData> {
+        self.ring_buffer.as_ref().map(RingBuffer::dump).unwrap_or_default()
     }
 
-    pub fn add_context(&mut self, tag: &str) -> Result<()> {
-        #[cfg(feature = "file")]
-        {
-            let log_path = self.config.context_log_path.join(tag).with_extension("log");
-            let file = BufWriter::new(
-                File::create(&log_path)
-                    .map_err(|e| eyre!("Unable to create context file at {}", e.to_string()))?,
-            );
+    /// Number of logs dropped across all async sinks because they couldn't keep up or gave up on them
+    #[cfg(feature = "sinks")]
+    pub fn async_sink_dropped_count(&self) -> u64 {
+        self.async_sinks.iter().map(AsyncSink::dropped_count).sum()
+    }
 
-            self.context_map.insert(tag.to_string(), file);
+    /// Adds a sink which can later be removed with [`LoggerThread::remove_sink`].
+    /// Replaces any sink already registered under the same name
+    pub fn add_named_sink<F>(&mut self, name: impl Into<String>, sink: F)
+    where
+        F: LogCallback + 'static,
+    {
+        let name = name.into();
+        #[cfg(feature = "sinks")]
+        {
+            self.sink_failures.get_mut().unwrap().reset_named(&name);
+            self.slow_sinks.get_mut().unwrap().reset_named(&name);
         }
+        self.named_sinks.insert(name, Box::new(sink));
+    }
 
-        Ok(())
+    /// [`LoggerThread::add_named_sink`] which is only called for logs at or above `min_level`
+    pub fn add_named_sink_with_level<F>(&mut self, name: impl Into<String>, sink: F, min_level: LogLevel)
+    where
+        F: LogCallback + 'static,
+    {
+        self.add_named_sink(name, with_min_level(sink, min_level));
     }
-    pub fn remove_context(&mut self, tag: &str) {
-        #[cfg(feature = "file")]
+
+    /// Returns whether a sink with this name was registered
+    pub fn remove_sink(&mut self, name: &str) -> bool {
+        #[cfg(feature = "sinks")]
         {
-            self.context_map.remove(tag);
+            self.sink_failures.get_mut().unwrap().reset_named(name);
+            self.slow_sinks.get_mut().unwrap().reset_named(name);
         }
+        self.named_sinks.remove(name).is_some()
     }
 
-    pub fn add_sink<F>(&mut self, sink: F)
+    pub fn get_named_sinks(&self) -> &HashMap<String, Box<dyn LogCallback>> {
+        &self.named_sinks
+    }
+
+    /// Adds a sink which receives each log along with its formatted line, for sinks that
+    /// forward text. The line is only rendered once however many of these sinks there are
+    pub fn add_formatted_sink<F>(&mut self, sink: F)
     where
-        F: LogCallback + 'static,
+        F: FormattedLogCallback + 'static,
     {
-        self.sinks.push(Box::new(sink));
+        #[cfg(feature = "sinks")]
+        {
+            self.sink_failures.get_mut().unwrap().reset_indexed();
+            self.slow_sinks.get_mut().unwrap().reset_indexed();
+        }
+        self.formatted_sinks.push(Box::new(sink));
     }
 
     fn log_thread(
-        log_queue: Arc<(SemaphoreLite, Mutex<Vec<LogData>>)>,
+        log_queue: Arc<(SemaphoreLite, Mutex<VecDeque<LogData>>)>,
+        queue_space: Arc<Condvar>,
         flush_semaphore: Arc<SemaphoreLite>,
+        flush_state: Arc<FlushState>,
+        writer_busy: Arc<AtomicBool>,
+        shutdown_flag: Arc<AtomicBool>,
         logger_thread: Arc<RwLock<LoggerThread>>,
     ) -> Result<()> {
         let mut logs_since_last_flush: usize = 0;
         let mut last_log_time = Instant::now();
+        let mut deduplicator = Deduplicator::default();
+        // last flush request completed by this thread
+        let mut flushed_request = 0;
+        // reused across rounds, so neither has to grow again once warmed up
+        let mut queue = VecDeque::new();
+        let mut batch = Vec::new();
 
         let log_mutex = &log_queue.1;
         let log_semaphore_lite = &log_queue.0;
 
         loop {
-            let max_str_len = logger_thread.read().unwrap().config.max_string_len;
+            // read before draining, so every log queued before this request is written below
+            let flush_request = flush_state.requested.load(Ordering::SeqCst);
+            // likewise taken before draining, so the logs queued before the signal are written
+            #[cfg(all(unix, feature = "signals"))]
+            let (signal, poll_signals) = {
+                let logger_thread = read_logger(&logger_thread);
+                (
+                    logger_thread.pending_signal.swap(0, Ordering::SeqCst),
+                    logger_thread.signal_handlers_installed.load(Ordering::SeqCst),
+                )
+            };
+
+            let (
+                chunk_options,
+                dedup,
+                max_queue_age,
+                track_write_latency,
+                flush_interval,
+                flush_log_count,
+                flush_on_level,
+                max_drain_batch,
+            ) = {
+                let logger_thread = read_logger(&logger_thread);
+                let config = &logger_thread.config;
+                (
+                    ChunkOptions::new(&logger_thread),
+                    config.dedup,
+                    config.max_queue_age,
+                    config.track_write_latency,
+                    config.flush_interval,
+                    config.flush_log_count,
+                    config.flush_on_level,
+                    match config.max_drain_batch {
+                        0 => usize::MAX,
+                        max_drain_batch => max_drain_batch,
+                    },
+                )
+            };
+
+            let mut queue_locked = log_mutex.lock().unwrap_or_else(PoisonError::into_inner);
+
+            // trade the queue for the buffer emptied last round, so producers only wait
+            // on a pointer swap rather than on the queue being copied out
+            std::mem::swap(&mut *queue_locked, &mut queue);
+            // decided under the same lock as the drain, re-locking later to check for
+            // emptiness left a window where a push could be missed until the next signal.
+            // Anything pushed after this point signals the semaphore, which stays set until
+            // the wait below, so it can't be lost either
+            let idle = queue.is_empty();
+            if !idle {
+                writer_busy.store(true, Ordering::SeqCst);
+            }
+            queue_space.notify_all();
+            drop(queue_locked);
+
+            // dropped before deduplication, so a stale log can't extend a run of fresh ones
+            if let Some(max_queue_age) = max_queue_age {
+                let logger = read_logger(&logger_thread);
+                let now = Utc::now();
+                let queued = queue.len();
+                queue.retain(|log| {
+                    let expired = log
+                        .enqueued_at
+                        .and_then(|enqueued_at| (now - enqueued_at).to_std().ok())
+                        .is_some_and(|age| age > max_queue_age);
+                    !expired || logger.is_guaranteed(log)
+                });
+                logger
+                    .expired_logs
+                    .fetch_add((queued - queue.len()) as u64, Ordering::Relaxed);
+            }
+
+            if dedup {
+                let logger = read_logger(&logger_thread);
+                let mut deduped = Vec::with_capacity(queue.len());
+                // a run which went quiet is summarized before anything newer
+                if deduplicator.is_expired() {
+                    deduped.extend(deduplicator.take_summary());
+                }
+                for log in queue.drain(..) {
+                    match logger.is_guaranteed(&log) {
+                        true => deduplicator.pass_through(log, &mut deduped),
+                        false => deduplicator.push(log, &mut deduped),
+                    }
+                }
+                queue.extend(deduped);
+            }
+
+            // the interval flush is checked between batches, so a burst can't hold it off
+            let mut remaining = queue.len();
+            let mut pending = queue.drain(..);
+            loop {
+                let len = remaining.min(max_drain_batch);
+                let mut urgent = false;
+                if len > 0 {
+                    batch.extend(split_str_into_chunks(pending.by_ref().take(len), chunk_options));
+                    // once per batch, the logs of a batch are written together
+                    if track_write_latency {
+                        let written_at = Some(Utc::now());
+                        batch.iter_mut().for_each(|log| log.written_at = written_at);
+                    }
+
+                    do_log_batch(&batch, logger_thread.clone())?;
+                    let guaranteed = {
+                        let logger = read_logger(&logger_thread);
+                        batch.iter().any(|log| logger.is_guaranteed(log))
+                    };
+                    urgent =
+                        guaranteed || flush_on_level.is_some_and(|level| batch.iter().any(|log| log.level >= level));
+                    batch.clear();
+                    remaining -= len;
+                    logs_since_last_flush += len;
+                    read_logger(&logger_thread)
+                        .logged
+                        .fetch_add(len as u64, Ordering::Relaxed);
+                }
 
-            let mut queue_locked = log_mutex.lock().unwrap();
+                let elapsed_time = last_log_time.elapsed() > flush_interval;
+                let exceeded_log_buffer = logs_since_last_flush > flush_log_count;
 
-            // move items from queue to local variable
-            let queue = Vec::from_iter(queue_locked.drain(..));
-            drop(queue_locked);
+                // under steady load the queue never runs empty, so flush here as well
+                if exceeded_log_buffer || elapsed_time || urgent {
+                    read_logger(&logger_thread).flush_files()?;
+                    logs_since_last_flush = 0;
+                    last_log_time = Instant::now();
+                }
 
-            if !queue.is_empty() {
-                let len = queue.len();
-                let split_logs = split_str_into_chunks(queue, max_str_len);
+                if remaining == 0 {
+                    break;
+                }
+            }
+            drop(pending);
+
+            // every log queued before `flush_request` was part of this round's queue and is
+            // written now, so the request completes without waiting for the queue to run empty,
+            // which it may never do under steady load
+            if !idle && flush_request > flushed_request {
+                read_logger(&logger_thread).flush_files()?;
+                flush_state.complete(flush_request);
+                flushed_request = flush_request;
+            }
 
-                for log in split_logs {
-                    do_log(log, logger_thread.clone())?;
+            // the batch above still went to the renamed files, the next one goes to the new ones
+            #[cfg(all(unix, feature = "signals", feature = "file"))]
+            {
+                let logger_thread = read_logger(&logger_thread);
+                if logger_thread.pending_reopen.swap(false, Ordering::SeqCst) {
+                    if let Err(e) = logger_thread.reopen_files() {
+                        logger_thread.queue_internal_log(LogLevel::Error, format!("{e:#}"));
+                    }
                 }
-                logs_since_last_flush += len;
             }
 
-            let elapsed_time = last_log_time.elapsed() > Duration::from_secs(1);
-            let exceeded_log_buffer = logs_since_last_flush > 50;
+            #[cfg(all(unix, feature = "signals"))]
+            if signal != 0 {
+                if let Some(summary) = deduplicator.take_summary() {
+                    do_log(summary, logger_thread.clone())?;
+                }
+                read_logger(&logger_thread).flush_files()?;
 
-            if exceeded_log_buffer || elapsed_time {
-                logs_since_last_flush = 0;
-                last_log_time = Instant::now();
+                // the installed handler replaced the default action, carry it out now the logs are safe
+                signal_hook::low_level::emulate_default_handler(signal as i32)
+                    .with_context(|| format!("Unable to carry out the default action of signal {signal}"))?;
             }
 
-            // wait for further logs if nothing left
-            if log_mutex.lock().unwrap().is_empty() {
-                #[cfg(feature = "file")]
-                {
-                    // flush file
-                    let mut logger_thread = logger_thread.write().unwrap();
-                    logger_thread.global_file.flush()?;
-                    logger_thread
-                        .context_map
-                        .values_mut()
-                        .try_for_each(|file| file.flush())?;
+            // wait for further logs once a drain came up empty
+            if idle {
+                let shutting_down = shutdown_flag.load(Ordering::SeqCst);
+                if shutting_down {
+                    if let Some(summary) = deduplicator.take_summary() {
+                        do_log(summary, logger_thread.clone())?;
+                    }
                 }
 
+                read_logger(&logger_thread).flush_files()?;
+                writer_busy.store(false, Ordering::SeqCst);
+
                 flush_semaphore.signal();
-                log_semaphore_lite.wait();
+                flush_state.complete(flush_request);
+                flushed_request = flush_request;
+
+                // the queue was drained and flushed above, nothing is left to lose
+                if shutting_down {
+                    return Ok(());
+                }
+
+                // wake up in time to write the summary of a pending run
+                let timeout = deduplicator.has_pending().then_some(DEDUP_TIMEOUT);
+                // signal handlers can't wake the writer, so it checks for caught signals regularly
+                #[cfg(all(unix, feature = "signals"))]
+                let timeout = match poll_signals {
+                    true => Some(timeout.map_or(SIGNAL_POLL_INTERVAL, |timeout| timeout.min(SIGNAL_POLL_INTERVAL))),
+                    false => timeout,
+                };
+                match timeout {
+                    Some(timeout) => log_semaphore_lite.wait_timeout(timeout),
+                    None => log_semaphore_lite.wait(),
+                }
+            }
+        }
+    }
+
+    ///
+    /// Has the writer thread drain the queue and flush every file, then waits for it.
+    /// Returns `false` if `timeout` elapsed before the flush completed.
+    ///
+    /// Once this returns `true`, every log queued before the call was written to the files
+    /// and handed to the sinks, even if other threads keep logging meanwhile. Async sinks
+    /// receive them on their own workers and may not be done with them yet.
+    ///
+    /// Without a running writer thread only the files are flushed, as nothing
+    /// would drain the queue.
+    pub fn flush(&self, timeout: Option<Duration>) -> Result<bool> {
+        if !self.inited.load(Ordering::SeqCst) || self.is_shutdown() || cfg!(feature = "disabled") {
+            self.flush_files()?;
+            return Ok(true);
+        }
+
+        let request = self.flush_state.requested.fetch_add(1, Ordering::SeqCst) + 1;
+        self.log_queue.0.signal();
+
+        let completed = self
+            .flush_state
+            .completed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let cond_var = &self.flush_state.cond_var;
+
+        let flushed = match timeout {
+            Some(timeout) => {
+                let (_, result) = cond_var
+                    .wait_timeout_while(completed, timeout, |completed| *completed < request)
+                    .unwrap_or_else(PoisonError::into_inner);
+                !result.timed_out()
+            }
+            None => {
+                let _ = cond_var
+                    .wait_while(completed, |completed| *completed < request)
+                    .unwrap_or_else(PoisonError::into_inner);
+                true
+            }
+        };
+
+        Ok(flushed)
+    }
+
+    ///
+    /// Blocks until the writer thread ran out of work: the queue is empty and every log it drained
+    /// was written and flushed. Returns `false` if `timeout` elapsed first, which steady logging
+    /// from other threads can cause, as the logger never goes idle then.
+    ///
+    /// Idleness is checked under the queue's lock, so a log queued concurrently is waited for
+    /// rather than missed, and `true` means the logger was idle at that moment. Async and tcp
+    /// sinks deliver on their own threads and may still be busy.
+    ///
+    /// Without a running writer thread nothing drains the queue, so this returns right away
+    /// whether it is empty.
+    pub fn wait_until_idle(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let queue = self.log_queue.1.lock().unwrap_or_else(PoisonError::into_inner);
+            if !self.is_writer_alive() {
+                return queue.is_empty();
+            }
+            if queue.is_empty() && !self.writer_busy.load(Ordering::SeqCst) {
+                return true;
+            }
+            drop(queue);
+
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return false,
+                },
+                None => None,
+            };
+            // completes once the writer thread flushed, which is where it goes idle
+            if !matches!(self.flush(remaining), Ok(true)) {
+                return false;
+            }
+        }
+    }
+
+    fn flush_files(&self) -> std::io::Result<()> {
+        #[cfg(feature = "file")]
+        {
+            if let Some(global_output) = self
+                .global_output
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_mut()
+            {
+                global_output.flush()?;
             }
+            if let Some(error_file) = self.error_file.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+                error_file.flush()?;
+            }
+            self.context_map
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .values_mut()
+                .try_for_each(|file| file.flush())?;
         }
+
+        Ok(())
     }
 
     ///
@@ -303,90 +2494,491 @@ impl LoggerThread {
     }
 }
 
-/// Split log message by line endings and then split each line into chunks
-fn split_str_into_chunks(queue: Vec<LogData>, max_str_len: usize) -> impl Iterator<Item = LogData> {
-    queue.into_iter().flat_map(move |log| {
-        // split log message by line endings
-        log.message
-            .split("\n")
-            .flat_map(|s| {
-                // split string into chunks
-                s.chars()
-                    .chunks(max_str_len)
-                    .into_iter()
-                    .map(|chunk| {
-                        let chunk = chunk.collect::<String>();
-                        LogData {
-                            message: chunk,
-                            ..log.clone()
-                        }
-                    })
-                    .collect_vec()
+/// Wraps `sink` so it is only called for logs at or above `min_level`
+fn with_min_level<F>(sink: F, min_level: LogLevel) -> impl LogCallback
+where
+    F: LogCallback,
+{
+    move |log: &LogData| match log.level >= min_level {
+        true => sink(log),
+        false => Ok(()),
+    }
+}
+
+#[cfg(feature = "file")]
+fn open_global_file(config: &LoggerConfig, log_path: &std::path::Path) -> Result<LogFile> {
+    create_log_dir(&config.context_log_path, "contexts")?;
+
+    // a bare file name has an empty parent, which is the working directory
+    if let Some(parent) = log_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if parent != config.context_log_path {
+            create_log_dir(parent, "global file")?;
+        }
+    }
+
+    LogFile::open(log_path, config).map_err(|e| {
+        eyre!(
+            "Unable to create global file at {}: {}",
+            log_path.display(),
+            e.to_string()
+        )
+    })
+}
+
+/// Opens `LoggerConfig::error_log_path`, creating its directory like the global file's
+#[cfg(feature = "file")]
+fn open_error_file(config: &LoggerConfig, error_log_path: &std::path::Path) -> Result<LogFile> {
+    if let Some(parent) = error_log_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_log_dir(parent, "error file")?;
+    }
+
+    LogFile::open(error_log_path, config)
+        .with_context(|| format!("Unable to create error file at {}", error_log_path.display()))
+}
+
+/// Creates the directory and its parents, failing clearly if a file is in the way
+#[cfg(feature = "file")]
+fn create_log_dir(path: &std::path::Path, purpose: &str) -> Result<()> {
+    if path.exists() && !path.is_dir() {
+        bail!(
+            "Logging directory for {purpose} {} exists but is not a directory",
+            path.display()
+        );
+    }
+
+    fs::create_dir_all(path)
+        .with_context(|| format!("Unable to make logging directory for {purpose} {}", path.display()))
+}
+
+/// The parts of [`LoggerConfig`] deciding how messages are split into lines
+#[derive(Debug, Clone, Copy)]
+struct ChunkOptions {
+    max_str_len: usize,
+    line_end: char,
+    long_message_policy: LongMessagePolicy,
+    split_on_graphemes: bool,
+    sanitize_control_chars: bool,
+}
+
+impl ChunkOptions {
+    fn new(logger_thread: &LoggerThread) -> Self {
+        let config = &logger_thread.config;
+
+        Self {
+            max_str_len: logger_thread.max_string_len(),
+            line_end: config.line_end,
+            long_message_policy: config.long_message_policy,
+            split_on_graphemes: config.split_on_graphemes,
+            sanitize_control_chars: config.sanitize_control_chars,
+        }
+    }
+}
+
+/// Split log message on `line_end` and then split each line into chunks, or truncate it.
+/// Every chunk is numbered with `chunk_index`/`chunk_total`, so a message can be
+/// reassembled from outputs which don't keep its chunks together.
+fn split_str_into_chunks(
+    queue: impl IntoIterator<Item = LogData>,
+    options: ChunkOptions,
+) -> impl Iterator<Item = LogData> {
+    let ChunkOptions {
+        max_str_len,
+        line_end,
+        long_message_policy,
+        split_on_graphemes,
+        sanitize_control_chars,
+    } = options;
+
+    queue.into_iter().flat_map(move |mut log| {
+        // taken out first, so copying the log for each chunk below doesn't copy the whole message too
+        let message = std::mem::take(&mut log.message);
+
+        // split log message by line endings, then each line into chunks
+        let chunks = message
+            .split(line_end)
+            // CRLF messages shouldn't leave a stray `\r` behind
+            .map(|s| match line_end {
+                '\n' => s.strip_suffix('\r').unwrap_or(s),
+                _ => s,
+            })
+            // escaped before chunking, so the escapes count towards `max_str_len`
+            .map(|s| match sanitize_control_chars {
+                true => escape_control_chars(s),
+                false => Cow::Borrowed(s),
+            })
+            .flat_map(|s| match long_message_policy {
+                LongMessagePolicy::Split => chunk_line(&s, max_str_len, split_on_graphemes),
+                LongMessagePolicy::Truncate => vec![truncate_line(&s, max_str_len, false, split_on_graphemes)],
+                LongMessagePolicy::TruncateWithEllipsis => {
+                    vec![truncate_line(&s, max_str_len, true, split_on_graphemes)]
+                }
             })
-            .collect_vec()
+            .collect_vec();
+        let chunk_total = chunks.len();
+        let mut log = Some(log);
+
+        chunks.into_iter().enumerate().map(move |(chunk_index, chunk)| {
+            // the last chunk gets the log itself, so a message which wasn't split is never copied
+            let log = match chunk_index + 1 == chunk_total {
+                true => log.take(),
+                false => log.clone(),
+            };
+            LogData {
+                message: chunk,
+                chunk_index,
+                chunk_total,
+                ..log.expect("only the last chunk takes the log")
+            }
+        })
+    })
+}
+
+/// Escapes every control char but tabs with [`char::escape_default`].
+/// The line is borrowed if there is nothing to escape
+fn escape_control_chars(line: &str) -> Cow<'_, str> {
+    let is_escaped = |c: char| c.is_control() && c != '\t';
+    if !line.chars().any(is_escaped) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut escaped = String::with_capacity(line.len() + 8);
+    for c in line.chars() {
+        match is_escaped(c) {
+            true => escaped.extend(c.escape_default()),
+            false => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Split a single line into chunks of at most `max_str_len` chars,
+/// or grapheme clusters if `split_on_graphemes` is set
+fn chunk_line(line: &str, max_str_len: usize, split_on_graphemes: bool) -> Vec<String> {
+    // validation rejects 0, but `chunks(0)` would panic and take the writer thread down with it
+    if max_str_len == 0 {
+        return vec![line.to_string()];
+    }
+
+    #[cfg(feature = "graphemes")]
+    if split_on_graphemes {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        return line
+            .graphemes(true)
+            .chunks(max_str_len)
+            .into_iter()
+            .map(|chunk| chunk.collect::<String>())
+            .collect_vec();
+    }
+    #[cfg(not(feature = "graphemes"))]
+    let _ = split_on_graphemes;
+
+    line.chars()
+        .chunks(max_str_len)
+        .into_iter()
+        .map(|chunk| chunk.collect::<String>())
+        .collect_vec()
+}
+
+/// Cut a single line to at most `max_str_len` chars, or grapheme clusters if `split_on_graphemes`
+/// is set. With `ellipsis`, a cut line ends in `…` and still fits `max_str_len`
+fn truncate_line(line: &str, max_str_len: usize, ellipsis: bool, split_on_graphemes: bool) -> String {
+    // same as splitting, 0 leaves the line alone
+    if max_str_len == 0 {
+        return line.to_string();
+    }
+
+    // byte offset at which each char or grapheme cluster starts
+    let starts = {
+        #[cfg(feature = "graphemes")]
+        if split_on_graphemes {
+            use unicode_segmentation::UnicodeSegmentation;
+
+            line.grapheme_indices(true).map(|(i, _)| i).collect_vec()
+        } else {
+            line.char_indices().map(|(i, _)| i).collect_vec()
+        }
+
+        #[cfg(not(feature = "graphemes"))]
+        {
+            let _ = split_on_graphemes;
+            line.char_indices().map(|(i, _)| i).collect_vec()
+        }
+    };
+
+    if starts.len() <= max_str_len {
+        return line.to_string();
+    }
+
+    match ellipsis {
+        true => format!("{}…", &line[..starts[max_str_len.saturating_sub(1)]]),
+        false => line[..starts[max_str_len]].to_string(),
+    }
+}
+
+/// Read locks the logger, recovering the lock if a thread panicked while holding it.
+/// None of the logger's own locks are held across user code, so its state stays usable
+pub(crate) fn read_logger(logger_thread: &RwLock<LoggerThread>) -> RwLockReadGuard<'_, LoggerThread> {
+    logger_thread.read().unwrap_or_else(|poisoned| {
+        let logger_thread = poisoned.into_inner();
+        logger_thread.warn_poison_recovered();
+        logger_thread
+    })
+}
+
+/// [`read_logger`] for the write lock
+pub(crate) fn write_logger(logger_thread: &RwLock<LoggerThread>) -> RwLockWriteGuard<'_, LoggerThread> {
+    logger_thread.write().unwrap_or_else(|poisoned| {
+        let logger_thread = poisoned.into_inner();
+        logger_thread.warn_poison_recovered();
+        logger_thread
     })
 }
 
 pub fn do_log(log: LogData, logger_thread: Arc<RwLock<LoggerThread>>) -> Result<()> {
+    // not queued, so it didn't reach the ring buffer yet
+    read_logger(&logger_thread).push_ring_buffer(&log);
+    do_log_batch(std::slice::from_ref(&log), logger_thread)
+}
+
+/// [`do_log`] for every log drained from the queue, writing each file once for the whole batch
+fn do_log_batch(logs: &[LogData], logger_thread: Arc<RwLock<LoggerThread>>) -> Result<()> {
     #[cfg(feature = "file")]
-    file_logger::do_log(&log, logger_thread.clone())?;
+    let flush_every_line = read_logger(&logger_thread).config.flush_every_line;
+    #[cfg(feature = "file")]
+    match flush_every_line {
+        // one line at a time, so none of them waits in a buffer for the rest of the batch
+        true => logs.iter().try_for_each(|log| {
+            file_logger::do_log(std::slice::from_ref(log), logger_thread.clone())?;
+            read_logger(&logger_thread).flush_files()
+        })?,
+        false => file_logger::do_log(logs, logger_thread.clone())?,
+    }
 
-    #[cfg(feature = "stdout")]
-    stdout_logger::do_log(&log);
+    for log in logs {
+        #[cfg(feature = "stdout")]
+        stdout_logger::do_log(log, logger_thread.clone())?;
 
-    #[cfg(all(target_os = "android", feature = "logcat"))]
-    logcat_logger::do_log(&log)?;
+        #[cfg(all(target_os = "android", feature = "logcat"))]
+        logcat_logger::do_log(log)?;
 
-    #[cfg(feature = "sinks")]
-    sink_logger::do_log(&log, logger_thread)?;
+        // a failing sink must not stop the writer thread, the sinks report their failures themselves
+        #[cfg(feature = "sinks")]
+        if let Err(report) = sink_logger::do_log(log, logger_thread.clone()) {
+            *read_logger(&logger_thread)
+                .last_sink_error
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(format!("{report:?}"));
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing_logger::do_log(log)?;
+    }
+
+    if let Some((threshold, hook)) = &read_logger(&logger_thread).alert_hook {
+        logs.iter()
+            .filter(|log| log.level >= *threshold && log.chunk_index == 0)
+            .for_each(hook);
+    }
+
+    Ok(())
+}
+
+// Mostly taken from https://doc.rust-lang.org/src/std/panicking.rs.html
+fn panic_message<'a>(info: &'a PanicHookInfo<'_>) -> &'a str {
+    match info.payload().downcast_ref::<&'static str>() {
+        Some(s) => *s,
+        None => match info.payload().downcast_ref::<String>() {
+            Some(s) => &s[..],
+            None => "Box<dyn Any>",
+        },
+    }
+}
+
+/// How long a panic waits for its log to reach the files
+const PANIC_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+///
+/// Sets a panic hook which queues the panic message, location and backtrace, then
+/// flushes the logger so the record is on disk even if the process aborts right after.
+/// The previously installed hook still runs afterwards.
+///
+/// Unlike [`panic_hook`], the logs go through [`LoggerThread::queue_log`], so they reach
+/// every output in order with the logs queued before the panic.
+pub fn install_panic_hook(logger: ThreadSafeLoggerThread) {
+    let previous_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        // a panic while the logger is write locked would deadlock on a blocking read
+        let logger_thread = match logger.try_read() {
+            Ok(logger_thread) => Some(logger_thread),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+
+        if let Some(logger_thread) = logger_thread {
+            let location = info.location();
+            let _ = logger_thread.queue_log(LogData {
+                level: LogLevel::Error,
+                tag: Some("panic".into()),
+                message: match location {
+                    Some(location) => format!("panicked at '{}', {}", panic_message(info), location),
+                    None => format!("panicked at '{}'", panic_message(info)),
+                },
+                file: location.map(|l| l.file().to_string()).unwrap_or_default().into(),
+                line: location.map_or(0, |l| l.line()),
+                column: location.map_or(0, |l| l.column()),
+                ..Default::default()
+            });
+
+            // whatever `RUST_BACKTRACE` says, the panic record is useless without one
+            #[cfg(feature = "backtrace")]
+            let _ = logger_thread.force_backtrace(LogLevel::Error);
+
+            // times out rather than hangs if the writer thread itself is panicking
+            let _ = logger_thread.flush(Some(PANIC_FLUSH_TIMEOUT));
+        }
+
+        previous_hook(info);
+    }));
+}
 
-    #[cfg(feature = "tracing")]
-    tracing_logger::do_log(&log)?;
+///
+/// Installs handlers for `signals`, e.g. `SIGTERM` and `SIGINT`, which have the writer thread
+/// drain the queue and flush every file before the signal's default action proceeds, so the
+/// last logs before a container is stopped aren't lost.
+///
+/// The handlers only set a flag, which the writer thread picks up within
+/// `SIGNAL_POLL_INTERVAL`, so they stay async-signal-safe. Fails if the writer thread isn't
+/// running, as nothing would carry out the default action then, and for signals which can't
+/// be handled like `SIGKILL`.
+///
+/// A second signal while the first one is still being handled carries out the default action
+/// right away, so a stuck flush can't keep the process from stopping. The handlers are removed
+/// when the writer thread exits, after [`LoggerThread::shutdown`] or a failure, restoring the
+/// default actions. A restarted writer thread needs them installed again.
+///
+/// Handlers of several loggers don't wait for each other, a signal terminating the process
+/// only waits for the writer thread which picks it up first. Install them on a single logger.
+#[cfg(all(unix, feature = "signals"))]
+pub fn install_signal_flush(logger: ThreadSafeLoggerThread, signals: &[i32]) -> Result<()> {
+    let logger_thread = read_logger(&logger);
+
+    register_signal_handlers(&logger_thread, |ids| {
+        for &signal in signals {
+            let context = || format!("Unable to install a handler for signal {signal}");
+            // runs before the flag below is set, so it only fires from the second signal on
+            ids.push(
+                signal_hook::flag::register_conditional_default(signal, Arc::clone(&logger_thread.signal_caught))
+                    .with_context(context)?,
+            );
+            ids.push(
+                signal_hook::flag::register(signal, Arc::clone(&logger_thread.signal_caught)).with_context(context)?,
+            );
+            ids.push(
+                signal_hook::flag::register_usize(signal, Arc::clone(&logger_thread.pending_signal), signal as usize)
+                    .with_context(context)?,
+            );
+        }
+
+        Ok(())
+    })
+}
+
+/// Registers handlers with `register` while the writer thread is running, keeping their ids
+/// so [`SignalHandlersGuard`] unregisters them once it exits. Any handlers registered before
+/// a failure are unregistered again
+#[cfg(all(unix, feature = "signals"))]
+fn register_signal_handlers(
+    logger_thread: &LoggerThread,
+    register: impl FnOnce(&mut Vec<signal_hook::SigId>) -> Result<()>,
+) -> Result<()> {
+    if !logger_thread.is_writer_alive() {
+        bail!("The writer thread is not running");
+    }
+
+    let mut ids = Vec::new();
+    let mut result = register(&mut ids);
+    let mut signal_ids = logger_thread.signal_ids.lock().unwrap_or_else(PoisonError::into_inner);
+    // checked under the lock the exiting writer thread takes after marking itself dead,
+    // so the ids are either unregistered by it or right here
+    if result.is_ok() && !logger_thread.is_writer_alive() {
+        result = Err(eyre!("The writer thread is not running"));
+    }
+    if result.is_err() {
+        for id in ids {
+            signal_hook::low_level::unregister(id);
+        }
+        return result;
+    }
+    signal_ids.extend(ids);
+    drop(signal_ids);
+
+    logger_thread.signal_handlers_installed.store(true, Ordering::SeqCst);
+    // an idle writer thread only starts polling once it wakes up
+    logger_thread.log_queue.0.signal();
 
     Ok(())
 }
 
+///
+/// Installs a `SIGHUP` handler which has the writer thread call [`LoggerThread::reopen_files`],
+/// as `logrotate` and similar tools expect after renaming the files. The process keeps running,
+/// and a failed reopen is logged rather than stopping the writer thread.
+///
+/// Like [`install_signal_flush`], the handler only sets a flag the writer thread picks up
+/// within `SIGNAL_POLL_INTERVAL`, it is removed again when the writer thread exits, and this
+/// fails if the writer thread isn't running.
+#[cfg(all(unix, feature = "signals", feature = "file"))]
+pub fn install_reopen_on_sighup(logger: ThreadSafeLoggerThread) -> Result<()> {
+    let logger_thread = read_logger(&logger);
+
+    register_signal_handlers(&logger_thread, |ids| {
+        ids.push(
+            signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&logger_thread.pending_reopen))
+                .context("Unable to install a handler for SIGHUP")?,
+        );
+
+        Ok(())
+    })
+}
+
 /// Returns a panic handler, optionally with backtrace and spantrace capture.
 pub fn panic_hook(
     backtrace: bool,
     spantrace: bool,
     logger_thread: Arc<RwLock<LoggerThread>>,
 ) -> Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync + 'static> {
-    // Mostly taken from https://doc.rust-lang.org/src/std/panicking.rs.html
     Box::new(move |info| {
         let location = info.location().unwrap();
-        let msg = match info.payload().downcast_ref::<&'static str>() {
-            Some(s) => *s,
-            None => match info.payload().downcast_ref::<String>() {
-                Some(s) => &s[..],
-                None => "Box<dyn Any>",
-            },
+        let msg = panic_message(info);
+        // the hook runs on the panicking thread
+        let thread = thread::current();
+
+        let base = LogData {
+            level: LogLevel::Error,
+            tag: Some("panic".into()),
+            file: file!().into(),
+            line: line!(),
+            column: column!(),
+            thread_id: Some(thread.id()),
+            thread_name: thread.name().map(str::to_string),
+            ..Default::default()
         };
 
         let _ = do_log(
             LogData {
-                level: LogLevel::Error,
-                tag: Some("panic".to_string()),
                 message: format!("panicked at '{}', {}", msg, location),
-                timestamp: Instant::now(),
-                file: file!().to_string(),
-                line: line!(),
-                column: column!(),
-                function_name: None,
+                ..base.clone()
             },
             logger_thread.clone(),
         );
         if backtrace {
             let _ = do_log(
                 LogData {
-                    level: LogLevel::Error,
-                    tag: Some("panic".to_string()),
                     message: format!("{:?}", Backtrace::force_capture()),
-                    timestamp: Instant::now(),
-                    file: file!().to_string(),
-                    line: line!(),
-                    column: column!(),
-                    function_name: None,
+                    timestamp: Utc::now(),
+                    ..base.clone()
                 },
                 logger_thread.clone(),
             );
@@ -398,14 +2990,9 @@ pub fn panic_hook(
 
             let _ = do_log(
                 LogData {
-                    level: LogLevel::Error,
-                    tag: Some("panic".to_string()),
                     message: format!("{:?}", SpanTrace::capture()),
-                    timestamp: Instant::now(),
-                    file: file!().to_string().into(),
-                    line: line!(),
-                    column: column!(),
-                    function_name: None,
+                    timestamp: Utc::now(),
+                    ..base
                 },
                 logger_thread.clone(),
             );
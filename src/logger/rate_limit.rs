@@ -0,0 +1,46 @@
+use std::time::Instant;
+
+/// Token bucket allowing bursts of up to `max_per_sec` logs, refilled continuously
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    max_per_sec: u32,
+    tokens: f64,
+    last_refill: Instant,
+    suppressed: u64,
+}
+
+pub(crate) enum RateLimitDecision {
+    Allow,
+    /// Allow, after reporting how many logs were suppressed since the last allowed one
+    AllowAfterSuppressed(u64),
+    Suppress,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max_per_sec: u32) -> Self {
+        Self {
+            max_per_sec,
+            tokens: max_per_sec as f64,
+            last_refill: Instant::now(),
+            suppressed: 0,
+        }
+    }
+
+    pub(crate) fn check(&mut self) -> RateLimitDecision {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * self.max_per_sec as f64;
+        self.tokens = (self.tokens + refill).min(self.max_per_sec as f64);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            self.suppressed += 1;
+            return RateLimitDecision::Suppress;
+        }
+        self.tokens -= 1.0;
+
+        match std::mem::take(&mut self.suppressed) {
+            0 => RateLimitDecision::Allow,
+            suppressed => RateLimitDecision::AllowAfterSuppressed(suppressed),
+        }
+    }
+}
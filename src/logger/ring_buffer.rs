@@ -0,0 +1,40 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, PoisonError},
+};
+
+use super::LogData;
+
+/// The last `capacity` logs, overwriting the oldest once full
+#[derive(Debug)]
+pub(crate) struct RingBuffer {
+    capacity: usize,
+    logs: Mutex<VecDeque<LogData>>,
+}
+
+impl RingBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            logs: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn push(&self, log: &LogData) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut logs = self.logs.lock().unwrap_or_else(PoisonError::into_inner);
+        if logs.len() == self.capacity {
+            logs.pop_front();
+        }
+        logs.push_back(log.clone());
+    }
+
+    /// Oldest first. Poisoning is ignored, as this is meant to be read while panicking
+    pub(crate) fn dump(&self) -> Vec<LogData> {
+        let logs = self.logs.lock().unwrap_or_else(PoisonError::into_inner);
+        logs.iter().cloned().collect()
+    }
+}
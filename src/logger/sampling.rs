@@ -0,0 +1,42 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
+};
+
+thread_local! {
+    // xorshift state per thread, so sampling never contends on a shared generator
+    static RNG_STATE: Cell<u64> = Cell::new(seed());
+}
+
+/// A random seed from std's per-process hash keys, mixed with the thread
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(std::process::id() as u64);
+    std::thread::current().id().hash(&mut hasher);
+
+    // xorshift gets stuck on 0
+    hasher.finish() | 1
+}
+
+/// Whether to keep a log sampled at `rate`, which is between 0.0 (drop all) and 1.0 (keep all)
+pub(crate) fn keep(rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    if rate <= 0.0 {
+        return false;
+    }
+
+    let random = RNG_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+
+    // the upper 53 bits as a float in [0, 1)
+    ((random >> 11) as f64 / (1u64 << 53) as f64) < rate
+}
@@ -1,16 +1,263 @@
-use crate::Result;
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, PoisonError},
+    time::{Duration, Instant},
+};
 
-use super::log_data::LogData;
+use color_eyre::eyre::eyre;
+use itertools::Itertools;
 
+use crate::{log_level::LogLevel, Result};
+
+use super::{log_data::LogData, FormattedLog};
+
+/// Consecutive failures of each sink, so a broken one can be disabled without touching the others.
+/// A sink without an entry hasn't failed since its last success
+#[derive(Debug, Default)]
+pub(crate) struct SinkFailures {
+    sinks: HashMap<usize, usize>,
+    named_sinks: HashMap<String, usize>,
+    formatted_sinks: HashMap<usize, usize>,
+}
+
+impl SinkFailures {
+    /// Forgets the failures of a named sink which was removed or replaced
+    pub(crate) fn reset_named(&mut self, name: &str) {
+        self.named_sinks.remove(name);
+    }
+
+    /// Forgets the failures of the sinks tracked by index, whose indices move when the sink lists change
+    pub(crate) fn reset_indexed(&mut self) {
+        self.sinks.clear();
+        self.formatted_sinks.clear();
+    }
+
+    fn get(&self, sink: SinkRef) -> usize {
+        let failures = match sink {
+            SinkRef::Sink(index) => self.sinks.get(&index),
+            SinkRef::Named(name) => self.named_sinks.get(name),
+            SinkRef::Formatted(index) => self.formatted_sinks.get(&index),
+        };
+        failures.copied().unwrap_or(0)
+    }
+
+    fn record(&mut self, sink: SinkRef, failed: bool) -> usize {
+        if !failed {
+            match sink {
+                SinkRef::Sink(index) => self.sinks.remove(&index),
+                SinkRef::Named(name) => self.named_sinks.remove(name),
+                SinkRef::Formatted(index) => self.formatted_sinks.remove(&index),
+            };
+            return 0;
+        }
+
+        let failures = match sink {
+            SinkRef::Sink(index) => self.sinks.entry(index).or_default(),
+            SinkRef::Named(name) => self.named_sinks.entry(name.to_string()).or_default(),
+            SinkRef::Formatted(index) => self.formatted_sinks.entry(index).or_default(),
+        };
+        *failures += 1;
+        *failures
+    }
+}
+
+/// How often the same sink is reported for being slow
+const SLOW_SINK_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When each sink was last reported for exceeding `LoggerConfig::slow_sink_threshold`
+#[derive(Debug, Default)]
+pub(crate) struct SlowSinks {
+    sinks: HashMap<usize, Instant>,
+    named_sinks: HashMap<String, Instant>,
+    formatted_sinks: HashMap<usize, Instant>,
+}
+
+impl SlowSinks {
+    /// Forgets a named sink which was removed or replaced
+    pub(crate) fn reset_named(&mut self, name: &str) {
+        self.named_sinks.remove(name);
+    }
+
+    /// Forgets the sinks tracked by index, whose indices move when the sink lists change
+    pub(crate) fn reset_indexed(&mut self) {
+        self.sinks.clear();
+        self.formatted_sinks.clear();
+    }
+
+    /// Whether a slow call of `sink` should be reported, at most once per `SLOW_SINK_WARN_INTERVAL`
+    fn should_warn(&mut self, sink: SinkRef, now: Instant) -> bool {
+        let warned = match sink {
+            SinkRef::Sink(index) => self.sinks.get(&index),
+            SinkRef::Named(name) => self.named_sinks.get(name),
+            SinkRef::Formatted(index) => self.formatted_sinks.get(&index),
+        };
+        if warned.is_some_and(|&warned| now.duration_since(warned) < SLOW_SINK_WARN_INTERVAL) {
+            return false;
+        }
+
+        match sink {
+            SinkRef::Sink(index) => self.sinks.insert(index, now),
+            SinkRef::Named(name) => self.named_sinks.insert(name.to_string(), now),
+            SinkRef::Formatted(index) => self.formatted_sinks.insert(index, now),
+        };
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SinkRef<'a> {
+    Sink(usize),
+    Named(&'a str),
+    Formatted(usize),
+}
+
+impl std::fmt::Display for SinkRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkRef::Sink(index) => write!(f, "sink #{index}"),
+            SinkRef::Named(name) => write!(f, "sink {name:?}"),
+            SinkRef::Formatted(index) => write!(f, "formatted sink #{index}"),
+        }
+    }
+}
+
+/// Hands `log` to every sink. A failing sink doesn't keep the others from receiving it,
+/// the failures are returned together once all sinks were called. The first failure in a row
+/// is logged, and a sink is disabled once it failed `sink_failure_limit` times in a row.
+/// With `slow_sink_threshold` set, each call is timed and a sink taking longer is logged
+/// once per `SLOW_SINK_WARN_INTERVAL`.
 pub(crate) fn do_log(
     log: &LogData,
     logger_thread: std::sync::Arc<std::sync::RwLock<super::LoggerThread>>,
 ) -> Result<()> {
-    let sinks = &logger_thread.read().unwrap().sinks;
+    let logger_thread = super::read_logger(&logger_thread);
+    let failure_limit = logger_thread.config.sink_failure_limit;
+    let slow_threshold = logger_thread.config.slow_sink_threshold;
+    // formatted on first use and shared by the formatted and tcp sinks
+    let mut line: Option<Arc<str>> = None;
+    let mut errors = Vec::new();
+
+    let sinks = logger_thread
+        .sinks
+        .iter()
+        .enumerate()
+        .map(|(index, sink)| (SinkRef::Sink(index), sink));
+    let named_sinks = logger_thread
+        .named_sinks
+        .iter()
+        .map(|(name, sink)| (SinkRef::Named(name), sink));
+    let formatted_sinks = logger_thread
+        .formatted_sinks
+        .iter()
+        .enumerate()
+        .map(|(index, sink)| (SinkRef::Formatted(index), sink));
+
+    let mut call = |index: usize, sink_ref: SinkRef, call_sink: &dyn Fn(&mut Option<Arc<str>>) -> Result<()>| {
+        let mut failures = logger_thread
+            .sink_failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if failure_limit > 0 && failures.get(sink_ref) >= failure_limit {
+            return;
+        }
+        drop(failures);
+
+        // recorded so a shutdown which times out can tell which sink hangs
+        logger_thread.sink_in_progress.store(index, Ordering::Relaxed);
+        let timing = slow_threshold.map(|threshold| (threshold, Instant::now()));
+        let result = call_sink(&mut line);
+
+        if let Some((threshold, started)) = timing {
+            let now = Instant::now();
+            let elapsed = now.duration_since(started);
+            if elapsed > threshold
+                && logger_thread
+                    .slow_sinks
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .should_warn(sink_ref, now)
+            {
+                logger_thread.queue_internal_log(
+                    LogLevel::Warn,
+                    format!("{sink_ref} took {elapsed:?} to handle a log, more than the {threshold:?} allowed"),
+                );
+            }
+        }
+
+        failures = logger_thread
+            .sink_failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let streak = failures.record(sink_ref, result.is_err());
+        drop(failures);
+
+        let Err(e) = result else {
+            return;
+        };
+        if streak == 1 {
+            logger_thread.queue_internal_log(LogLevel::Warn, format!("{sink_ref} failed: {e:#}"));
+        }
+        if streak == failure_limit {
+            logger_thread.queue_internal_log(
+                LogLevel::Error,
+                format!("{sink_ref} failed {streak} times in a row and was disabled: {e:#}"),
+            );
+        }
+        errors.push(e.wrap_err(format!("{sink_ref} failed")));
+    };
 
-    for sink in sinks {
-        sink(log)?;
+    // the flat index follows the order `LoggerThread::sink_in_progress` expects
+    let mut index = 0;
+    for (sink_ref, sink) in sinks.chain(named_sinks) {
+        call(index, sink_ref, &|_| sink(log));
+        index += 1;
     }
+    for (sink_ref, sink) in formatted_sinks {
+        call(index, sink_ref, &|line| {
+            let line = formatted_line(line, log, &logger_thread)?;
+            sink(&FormattedLog { log, line })
+        });
+        index += 1;
+    }
+    logger_thread
+        .sink_in_progress
+        .store(super::NO_SINK_IN_PROGRESS, Ordering::Relaxed);
+
+    for async_sink in &logger_thread.async_sinks {
+        async_sink.send(log);
+    }
+
+    #[cfg(feature = "tcp")]
+    if !logger_thread.tcp_sinks.is_empty() {
+        match formatted_line(&mut line, log, &logger_thread) {
+            Ok(line) => {
+                for tcp_sink in &logger_thread.tcp_sinks {
+                    tcp_sink.send(line.as_bytes().to_vec());
+                }
+            }
+            Err(e) => errors.push(e.wrap_err("Unable to format the line of the tcp sinks")),
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        count => Err(eyre!(
+            "{count} sinks failed: {}",
+            errors.iter().map(|e| format!("{e:#}")).join("; ")
+        )),
+    }
+}
+
+/// `log` rendered like the file output, formatting it only if `line` is still empty
+fn formatted_line(line: &mut Option<Arc<str>>, log: &LogData, logger_thread: &super::LoggerThread) -> Result<Arc<str>> {
+    if let Some(line) = line {
+        return Ok(line.clone());
+    }
+
+    let mut bytes = Vec::new();
+    logger_thread.formatter.format(log, &mut bytes)?;
+    logger_thread.config.line_ending.terminate(&mut bytes);
 
-    Ok(())
+    Ok(line.insert(String::from_utf8_lossy(&bytes).into()).clone())
 }
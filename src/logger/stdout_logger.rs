@@ -1,9 +1,78 @@
-use super::LogData;
+use std::{
+    ffi::OsStr,
+    io::{IsTerminal, Write},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use super::{read_logger, ColorMode, LogData, LoggerThread, StdStreamPolicy};
+use crate::log_level::LogLevel;
 
 // assert tracing is not enabled
 #[cfg(not(feature = "tracing"))]
 compile_error!("The 'tracing' feature must be enabled to use this logger.");
 
-pub(crate) fn do_log(log: &LogData) {
-    println!("{}\n", log.format());
+pub(crate) fn do_log(log: &LogData, logger_thread: Arc<RwLock<LoggerThread>>) -> std::io::Result<()> {
+    write_log(log, &read_logger(&logger_thread))
+}
+
+/// [`do_log`] for callers already holding the logger
+pub(crate) fn write_log(log: &LogData, logger_thread: &LoggerThread) -> std::io::Result<()> {
+    let config = &logger_thread.config;
+
+    let to_stderr = match config.std_stream {
+        StdStreamPolicy::AllStdout => false,
+        StdStreamPolicy::ErrWarnToStderr => matches!(log.level, LogLevel::Error | LogLevel::Warn),
+        StdStreamPolicy::AllStderr => true,
+    };
+    let colored = match config.color {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => env_color_override().unwrap_or_else(|| match to_stderr {
+            true => std::io::stderr().is_terminal(),
+            false => std::io::stdout().is_terminal(),
+        }),
+    };
+
+    let mut log_line = Vec::new();
+    match colored {
+        true => logger_thread.formatter.format_colored(log, &mut log_line)?,
+        false => logger_thread.formatter.format(log, &mut log_line)?,
+    }
+    config.line_ending.terminate(&mut log_line);
+
+    match to_stderr {
+        true => std::io::stderr().lock().write_all(&log_line),
+        false => std::io::stdout().lock().write_all(&log_line),
+    }
+}
+
+/// The `NO_COLOR`/`CLICOLOR` conventions for [`ColorMode::Auto`], read once per process
+fn env_color_override() -> Option<bool> {
+    static OVERRIDE: OnceLock<Option<bool>> = OnceLock::new();
+    *OVERRIDE.get_or_init(|| {
+        color_override(
+            std::env::var_os("NO_COLOR").as_deref(),
+            std::env::var_os("CLICOLOR_FORCE").as_deref(),
+            std::env::var_os("CLICOLOR").as_deref(),
+        )
+    })
+}
+
+/// A non-empty `NO_COLOR` disables color, a `CLICOLOR_FORCE` other than `0` forces it,
+/// and `CLICOLOR=0` disables it. `None` leaves it to whether the stream is a terminal
+pub(crate) fn color_override(
+    no_color: Option<&OsStr>,
+    clicolor_force: Option<&OsStr>,
+    clicolor: Option<&OsStr>,
+) -> Option<bool> {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        return Some(false);
+    }
+    if clicolor_force.is_some_and(|value| !value.is_empty() && value != "0") {
+        return Some(true);
+    }
+    if clicolor.is_some_and(|value| value == "0") {
+        return Some(false);
+    }
+    None
 }
@@ -0,0 +1,155 @@
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use chrono::SecondsFormat;
+use color_eyre::eyre::{eyre, Context};
+
+use super::LogData;
+use crate::{log_level::LogLevel, Result};
+
+// user-level messages
+const FACILITY: u8 = 1;
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogTransport {
+    Udp,
+    /// Messages are framed by octet counting, see RFC 6587
+    Tcp,
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// Forwards logs as RFC 5424 messages. Meant to run on an async sink worker,
+/// since sending blocks while the connection is retried
+pub(crate) struct SyslogSink {
+    addr: SocketAddr,
+    transport: SyslogTransport,
+    hostname: String,
+    pid: u32,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl SyslogSink {
+    pub(crate) fn new(addr: impl ToSocketAddrs, transport: SyslogTransport) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()
+            .context("Unable to resolve syslog address")?
+            .next()
+            .ok_or_else(|| eyre!("Syslog address resolved to nothing"))?;
+
+        Ok(Self {
+            addr,
+            transport,
+            hostname: hostname(),
+            pid: std::process::id(),
+            connection: Mutex::new(None),
+        })
+    }
+
+    /// Sends the log, reconnecting with exponential backoff if the connection fails
+    pub(crate) fn send(&self, log: &LogData) -> Result<()> {
+        let message = self.format(log);
+        let mut connection = self.connection.lock().unwrap();
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = self
+                .connected(&mut connection)
+                .and_then(|connection| self.write(connection, &message));
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt == MAX_ATTEMPTS => {
+                    return Err(e).with_context(|| format!("Unable to send log to syslog at {}", self.addr));
+                }
+                Err(_) => {
+                    *connection = None;
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn connected<'a>(&self, connection: &'a mut Option<Connection>) -> std::io::Result<&'a mut Connection> {
+        if connection.is_none() {
+            *connection = Some(self.connect()?);
+        }
+
+        Ok(connection.as_mut().unwrap())
+    }
+
+    fn connect(&self) -> std::io::Result<Connection> {
+        match self.transport {
+            SyslogTransport::Udp => {
+                let bind_addr = match self.addr {
+                    SocketAddr::V4(_) => "0.0.0.0:0",
+                    SocketAddr::V6(_) => "[::]:0",
+                };
+                let socket = UdpSocket::bind(bind_addr)?;
+                socket.connect(self.addr)?;
+                Ok(Connection::Udp(socket))
+            }
+            SyslogTransport::Tcp => Ok(Connection::Tcp(TcpStream::connect(self.addr)?)),
+        }
+    }
+
+    fn write(&self, connection: &mut Connection, message: &str) -> std::io::Result<()> {
+        match connection {
+            Connection::Udp(socket) => socket.send(message.as_bytes()).map(|_| ()),
+            Connection::Tcp(stream) => write!(stream, "{} {message}", message.len()),
+        }
+    }
+
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+    fn format(&self, log: &LogData) -> String {
+        let severity = match log.level {
+            LogLevel::Error => 3,
+            LogLevel::Warn => 4,
+            LogLevel::Info => 6,
+            LogLevel::Debug => 7,
+        };
+
+        format!(
+            "<{}>1 {} {} {} {} - - {}",
+            FACILITY * 8 + severity,
+            log.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            header_field(log.tag.as_deref().unwrap_or_default(), 48),
+            self.pid,
+            log.message
+        )
+    }
+}
+
+/// Header fields are limited printable ASCII without spaces, `-` when empty
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value.chars().filter(|c| c.is_ascii_graphic()).take(max_len).collect();
+
+    match field.is_empty() {
+        true => "-".to_string(),
+        false => field,
+    }
+}
+
+fn hostname() -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .unwrap_or_default();
+
+    header_field(hostname.trim(), 255)
+}
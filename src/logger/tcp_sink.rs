@@ -0,0 +1,142 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::Result;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Writes formatted lines to a TCP socket from its own worker thread.
+/// Up to `buffer` lines are kept while disconnected, older ones are dropped after that
+pub(crate) struct TcpSink {
+    sender: Option<SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl TcpSink {
+    pub(crate) fn spawn(addr: SocketAddr, buffer: usize) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(buffer);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let worker_dropped = dropped.clone();
+        let worker = thread::Builder::new()
+            .name("paper2-tcp-sink".to_string())
+            .spawn(move || run(addr, receiver, buffer, &worker_dropped))?;
+
+        Ok(Self {
+            sender: Some(sender),
+            worker: Some(worker),
+            dropped,
+        })
+    }
+
+    /// Hands the line to the worker, dropping it if the worker fell `buffer` lines behind
+    pub(crate) fn send(&self, line: Vec<u8>) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = sender.try_send(line) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TcpSink {
+    fn drop(&mut self) {
+        // closing the channel has the worker make a last attempt at the pending lines and exit
+        self.sender.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run(addr: SocketAddr, receiver: Receiver<Vec<u8>>, buffer: usize, dropped: &AtomicU64) {
+    let mut pending = VecDeque::new();
+    let mut stream: Option<TcpStream> = None;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut retry_at = Instant::now();
+    let mut closed = false;
+
+    let retain = |pending: &mut VecDeque<Vec<u8>>, line| {
+        if pending.len() >= buffer.max(1) {
+            pending.pop_front();
+            dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        pending.push_back(line);
+    };
+
+    loop {
+        if pending.is_empty() {
+            match receiver.recv() {
+                Ok(line) => pending.push_back(line),
+                Err(_) => return,
+            }
+        }
+        while let Ok(line) = receiver.try_recv() {
+            retain(&mut pending, line);
+        }
+
+        // once the sink is dropped there is one last attempt, regardless of the backoff
+        if stream.is_none() && (closed || Instant::now() >= retry_at) {
+            stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok();
+        }
+
+        let written = match &mut stream {
+            Some(connected) => write_pending(connected, &mut pending).is_ok(),
+            None => false,
+        };
+        if written {
+            backoff = INITIAL_BACKOFF;
+            match closed {
+                true => return,
+                false => continue,
+            }
+        }
+
+        if closed {
+            dropped.fetch_add(pending.len() as u64, Ordering::Relaxed);
+            return;
+        }
+
+        if stream.take().is_some() || Instant::now() >= retry_at {
+            retry_at = Instant::now() + backoff;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+
+        // keep taking lines while waiting to reconnect, so the writer thread never blocks
+        match receiver.recv_timeout(retry_at.saturating_duration_since(Instant::now())) {
+            Ok(line) => retain(&mut pending, line),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => closed = true,
+        }
+    }
+}
+
+/// Lines are only taken off `pending` once written, so a failed write is retried after reconnecting
+fn write_pending(stream: &mut TcpStream, pending: &mut VecDeque<Vec<u8>>) -> std::io::Result<()> {
+    while let Some(line) = pending.front() {
+        stream.write_all(line)?;
+        pending.pop_front();
+    }
+
+    stream.flush()
+}
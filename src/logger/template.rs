@@ -0,0 +1,185 @@
+use std::io::{self, Write};
+
+use color_eyre::eyre::{bail, eyre};
+
+use super::{LogData, LogFormatter, LoggerConfig};
+use crate::Result;
+
+/// A field of [`LogData`] a template can refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Level,
+    Time,
+    Tag,
+    Message,
+    File,
+    Line,
+    Column,
+    Function,
+    Thread,
+    Fields,
+    Seq,
+    Latency,
+    Chunk,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Text between placeholders, as well as `{pid}` and `{hostname}` which never change
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// Renders text lines from a template like `"{level} {time} {tag}: {message}"`, set with
+/// `LoggerConfig::line_template`. The template is parsed once, each line then only fills it in.
+///
+/// Placeholders are `{level}`, `{time}` (in `timestamp_format`), `{tag}` (`default_tag` for untagged
+/// logs), `{message}`, `{file}`, `{line}`, `{column}`, `{function}`, `{thread}` (name, or id if unnamed),
+/// `{fields}` (` key=value` per field), `{seq}`, `{latency}` (`1.234ms` with
+/// `LoggerConfig::track_write_latency`), `{chunk}` (`index/total` of split messages),
+/// `{pid}` and `{hostname}` (requires the `hostname` feature). `{{` and `}}` write a literal brace
+#[derive(Debug, Clone)]
+pub struct TemplateFormatter {
+    segments: Vec<Segment>,
+    timestamp_format: String,
+    use_local_time: bool,
+    default_tag: Option<String>,
+}
+
+impl TemplateFormatter {
+    /// Parses `template`, taking the timestamp and default tag settings from `config`.
+    /// Fails on unknown placeholders and unmatched braces
+    pub fn new(template: &str, config: &LoggerConfig) -> Result<Self> {
+        Ok(Self {
+            segments: parse(template)?,
+            timestamp_format: config.timestamp_format.clone(),
+            use_local_time: config.use_local_time,
+            default_tag: config.default_tag.clone(),
+        })
+    }
+}
+
+impl LogFormatter for TemplateFormatter {
+    fn format(&self, data: &LogData, out: &mut dyn Write) -> io::Result<()> {
+        for segment in &self.segments {
+            let placeholder = match segment {
+                Segment::Literal(text) => {
+                    out.write_all(text.as_bytes())?;
+                    continue;
+                }
+                Segment::Placeholder(placeholder) => placeholder,
+            };
+
+            match placeholder {
+                Placeholder::Level => write!(out, "{}", data.level)?,
+                Placeholder::Time => {
+                    let time = data.format_timestamp(&self.timestamp_format, self.use_local_time);
+                    out.write_all(time.as_bytes())?;
+                }
+                Placeholder::Tag => {
+                    let tag = data.tag.as_deref().or(self.default_tag.as_deref()).unwrap_or_default();
+                    out.write_all(tag.as_bytes())?;
+                }
+                Placeholder::Message => out.write_all(data.message.as_bytes())?,
+                Placeholder::File => out.write_all(data.file.as_bytes())?,
+                Placeholder::Line => write!(out, "{}", data.line)?,
+                Placeholder::Column => write!(out, "{}", data.column)?,
+                Placeholder::Function => {
+                    out.write_all(data.function_name.as_deref().unwrap_or("default").as_bytes())?
+                }
+                Placeholder::Thread => match (&data.thread_name, data.thread_id) {
+                    (Some(name), _) => out.write_all(name.as_bytes())?,
+                    (None, Some(thread_id)) => write!(out, "{thread_id:?}")?,
+                    (None, None) => {}
+                },
+                Placeholder::Fields => {
+                    for (key, value) in &data.fields {
+                        write!(out, " {key}={value}")?;
+                    }
+                }
+                Placeholder::Seq => write!(out, "{}", data.seq)?,
+                Placeholder::Latency => {
+                    if let Some(latency) = data.write_latency() {
+                        write!(out, "{:.3}ms", latency.as_secs_f64() * 1000.0)?;
+                    }
+                }
+                Placeholder::Chunk if data.chunk_total > 1 => {
+                    write!(out, "{}/{}", data.chunk_index + 1, data.chunk_total)?
+                }
+                Placeholder::Chunk => {}
+            }
+        }
+
+        writeln!(out)
+    }
+}
+
+fn parse(template: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| eyre!("Unclosed placeholder in line template {template:?}"))?;
+                let name = &rest[..end];
+                chars = rest[end + 1..].chars();
+
+                match placeholder(name)? {
+                    Segment::Literal(text) => literal.push_str(&text),
+                    segment => {
+                        if !literal.is_empty() {
+                            segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                        }
+                        segments.push(segment);
+                    }
+                }
+            }
+            '}' => bail!("Unmatched `}}` in line template {template:?}, write `}}}}` for a literal one"),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    Ok(segments)
+}
+
+fn placeholder(name: &str) -> Result<Segment> {
+    let placeholder = match name {
+        "level" => Placeholder::Level,
+        "time" => Placeholder::Time,
+        "tag" => Placeholder::Tag,
+        "message" => Placeholder::Message,
+        "file" => Placeholder::File,
+        "line" => Placeholder::Line,
+        "column" => Placeholder::Column,
+        "function" => Placeholder::Function,
+        "thread" => Placeholder::Thread,
+        "fields" => Placeholder::Fields,
+        "seq" => Placeholder::Seq,
+        "latency" => Placeholder::Latency,
+        "chunk" => Placeholder::Chunk,
+        "pid" => return Ok(Segment::Literal(std::process::id().to_string())),
+        #[cfg(feature = "hostname")]
+        "hostname" => return Ok(Segment::Literal(super::hostname().unwrap_or_default().to_string())),
+        #[cfg(not(feature = "hostname"))]
+        "hostname" => bail!("The {{hostname}} placeholder requires the `hostname` feature"),
+        name => bail!("Unknown placeholder {{{name}}} in line template"),
+    };
+
+    Ok(Segment::Placeholder(placeholder))
+}
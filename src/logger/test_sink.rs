@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{LogCallback, LogData};
+use crate::log_level::LogLevel;
+
+/// Collects every log it receives so tests can inspect what was emitted.
+/// Clones share the same logs, so keep one and attach the other with
+/// `logger.add_sink(sink.callback())`
+#[derive(Debug, Clone, Default)]
+pub struct TestSink {
+    logs: Arc<Mutex<Vec<LogData>>>,
+}
+
+impl TestSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink for [`super::LoggerThread::add_sink`] which records into this `TestSink`
+    pub fn callback(&self) -> impl LogCallback + 'static {
+        let logs = self.logs.clone();
+        move |log: &LogData| {
+            logs.lock().unwrap_or_else(PoisonError::into_inner).push(log.clone());
+            Ok(())
+        }
+    }
+
+    /// Every log received so far, oldest first
+    pub fn logs(&self) -> Vec<LogData> {
+        self.logs.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub fn logged_messages(&self) -> Vec<String> {
+        let logs = self.logs.lock().unwrap_or_else(PoisonError::into_inner);
+        logs.iter().map(|log| log.message.clone()).collect()
+    }
+
+    /// Whether a log of `level` containing `substring` was received
+    pub fn contains(&self, level: LogLevel, substring: &str) -> bool {
+        let logs = self.logs.lock().unwrap_or_else(PoisonError::into_inner);
+        logs.iter()
+            .any(|log| log.level == level && log.message.contains(substring))
+    }
+
+    /// Panics listing what was logged unless a log of `level` containing `substring` was received.
+    /// Logs are written by the logger thread, so flush the logger before asserting
+    #[track_caller]
+    pub fn assert_logged(&self, level: LogLevel, substring: &str) {
+        if !self.contains(level, substring) {
+            let logs = self.logs.lock().unwrap_or_else(PoisonError::into_inner);
+            let logged = logs
+                .iter()
+                .map(|log| format!("  {:?}: {}", log.level, log.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            panic!("expected a {level:?} log containing {substring:?}, logged:\n{logged}");
+        }
+    }
+
+    /// Forgets every log received so far
+    pub fn clear(&self) {
+        self.logs.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
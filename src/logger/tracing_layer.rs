@@ -0,0 +1,142 @@
+use std::fmt;
+
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{log_level::LogLevel, ThreadSafeLoggerThread};
+
+use super::{read_logger, tracing_logger, LogData};
+
+impl From<tracing::Level> for LogLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => LogLevel::Error,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::DEBUG | tracing::Level::TRACE => LogLevel::Debug,
+        }
+    }
+}
+
+///
+/// A `tracing_subscriber` layer queueing every event on the logger thread.
+/// The event's target becomes the tag and its `message` the message, other event fields
+/// are kept as structured fields. Fields of the spans the event happened in come first,
+/// after a `spans` field listing their names from the outermost, e.g. `spans=request:db`.
+///
+/// Logs forwarded to `tracing` by the logger itself are skipped, so they don't loop back.
+pub struct PaperlogLayer {
+    logger_thread: ThreadSafeLoggerThread,
+}
+
+impl PaperlogLayer {
+    pub fn new(logger_thread: ThreadSafeLoggerThread) -> Self {
+        Self { logger_thread }
+    }
+}
+
+/// Recorded fields of a span, kept in its extensions
+struct SpanFields(Vec<(String, String)>);
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+impl FieldVisitor {
+    fn push(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = Some(value),
+            name => self.fields.push((name.to_string(), value)),
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{value:?}"));
+    }
+}
+
+impl<S> Layer<S> for PaperlogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor {
+            message: None,
+            fields: std::mem::take(fields),
+        };
+        values.record(&mut visitor);
+        *fields = visitor.fields;
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() == tracing_logger::FORWARD_TARGET {
+            return;
+        }
+
+        let logger_thread = read_logger(&self.logger_thread);
+        let level = (*metadata.level()).into();
+
+        if !logger_thread.is_level_enabled(level) {
+            return;
+        }
+
+        let mut fields = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut span_names = Vec::new();
+            for span in scope.from_root() {
+                span_names.push(span.name());
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.iter().cloned());
+                }
+            }
+            fields.insert(0, ("spans".to_string(), span_names.join(":")));
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        fields.extend(visitor.fields);
+
+        // `Layer::on_event` has no way to report failure
+        let _ = logger_thread.queue_log(LogData {
+            level,
+            tag: Some(metadata.target().into()),
+            message: visitor.message.unwrap_or_default(),
+            file: metadata.file().unwrap_or_default().into(),
+            line: metadata.line().unwrap_or_default(),
+            fields,
+            ..Default::default()
+        });
+    }
+}
@@ -3,6 +3,10 @@ use tracing::{debug, error, event, info, warn};
 
 use crate::log_level::LogLevel;
 
+/// Target of the events logs are forwarded as, so [`PaperlogLayer`](super::tracing_layer::PaperlogLayer)
+/// can skip them instead of queueing them again
+pub(crate) const FORWARD_TARGET: &str = module_path!();
+
 impl From<LogLevel> for tracing::Level {
     fn from(level: LogLevel) -> Self {
         match level {
@@ -0,0 +1,124 @@
+use std::{borrow::Cow, fmt::Arguments};
+
+use crate::{
+    get_logger,
+    log_level::LogLevel,
+    logger::{read_logger, LogData},
+};
+
+/// `false` with the `disabled` feature, which compiles the logging macros down to nothing
+/// while their arguments still type-check
+#[doc(hidden)]
+pub const LOGGING_COMPILED_IN: bool = !cfg!(feature = "disabled");
+
+/// Whether the global logger exists and would queue a log of this level, see
+/// [`LoggerThread::is_level_enabled`](crate::LoggerThread::is_level_enabled).
+/// The logging macros check this before evaluating their arguments
+pub fn is_level_enabled(level: LogLevel) -> bool {
+    get_logger().is_some_and(|logger| read_logger(&logger).is_level_enabled(level))
+}
+
+/// Queues a log on the global logger, used by [`log_info!`] and friends.
+/// The message is only formatted if the logger exists and the level is enabled
+#[doc(hidden)]
+pub fn log_with_location(
+    level: LogLevel,
+    tag: Option<Cow<'static, str>>,
+    message: Arguments<'_>,
+    file: &'static str,
+    line: u32,
+    column: u32,
+    function_name: &'static str,
+) {
+    let Some(logger) = get_logger() else {
+        return;
+    };
+    let logger = read_logger(&logger);
+    if !logger.is_level_enabled(level) {
+        return;
+    }
+
+    // macros have nowhere to report a failure to
+    let _ = logger.queue_log(LogData::new(
+        level,
+        tag,
+        message.to_string(),
+        file,
+        line,
+        column,
+        Some(function_name.to_string()),
+    ));
+}
+
+/// Name of the surrounding function, e.g. `my_crate::net::connect`
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __function_name {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        let name = type_name_of(f);
+        name.strip_suffix("::f").unwrap_or(name)
+    }};
+}
+
+/// Logs to the global logger at the given level, capturing the source location and function.
+/// `log!(LogLevel::Info, tag: "net", "connected to {}", addr)`, the tag is optional
+#[macro_export]
+macro_rules! log {
+    ($level:expr, tag: $tag:expr, $($arg:tt)+) => {{
+        let level = $level;
+        // the tag and arguments are only evaluated for enabled levels
+        if $crate::LOGGING_COMPILED_IN && $crate::is_level_enabled(level) {
+            $crate::log_with_location(
+                level,
+                ::std::option::Option::Some(::std::borrow::Cow::from($tag)),
+                ::std::format_args!($($arg)+),
+                ::std::file!(),
+                ::std::line!(),
+                ::std::column!(),
+                $crate::__function_name!(),
+            )
+        }
+    }};
+    ($level:expr, $($arg:tt)+) => {{
+        let level = $level;
+        if $crate::LOGGING_COMPILED_IN && $crate::is_level_enabled(level) {
+            $crate::log_with_location(
+                level,
+                ::std::option::Option::None,
+                ::std::format_args!($($arg)+),
+                ::std::file!(),
+                ::std::line!(),
+                ::std::column!(),
+                $crate::__function_name!(),
+            )
+        }
+    }};
+}
+
+/// [`log!`] at [`LogLevel::Debug`](crate::LogLevel::Debug)
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Debug, $($arg)+) };
+}
+
+/// [`log!`] at [`LogLevel::Info`](crate::LogLevel::Info)
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Info, $($arg)+) };
+}
+
+/// [`log!`] at [`LogLevel::Warn`](crate::LogLevel::Warn)
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Warn, $($arg)+) };
+}
+
+/// [`log!`] at [`LogLevel::Error`](crate::LogLevel::Error)
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => { $crate::log!($crate::LogLevel::Error, $($arg)+) };
+}
@@ -43,6 +43,7 @@ fn test_log_init() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/1"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -69,6 +70,7 @@ fn test_log_output() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/2"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -76,16 +78,20 @@ fn test_log_output() -> Result<()> {
     thread::sleep(Duration::from_millis(2));
 
     let output = {
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Info,
-            tag: None,
-            message: "hi! 5".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: "hi! 5".to_owned(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            })
+            .unwrap();
         wait_for_complete_flush(&logger.read().unwrap());
     };
 
@@ -101,6 +107,7 @@ fn test_single_thread_log_spam() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/3"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -109,29 +116,37 @@ fn test_single_thread_log_spam() -> Result<()> {
 
     let output = {
         let start = Instant::now();
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Debug,
-            tag: None,
-            message: "Spam logging now!".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-
-            ..Default::default()
-        });
-
-        for i in 0..100000 {
-            logger.read().unwrap().queue_log(LogData {
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
                 level: LogLevel::Debug,
                 tag: None,
-                message: format!("log i {i}"),
-                file: file!().to_string(),
+                message: "Spam logging now!".to_owned(),
+                file: file!().into(),
                 line: line!(),
                 column: column!(),
                 function_name: None,
+
                 ..Default::default()
-            });
+            })
+            .unwrap();
+
+        for i in 0..100000 {
+            logger
+                .read()
+                .unwrap()
+                .queue_log(LogData {
+                    level: LogLevel::Debug,
+                    tag: None,
+                    message: format!("log i {i}"),
+                    file: file!().into(),
+                    line: line!(),
+                    column: column!(),
+                    function_name: None,
+                    ..Default::default()
+                })
+                .unwrap();
         }
         wait_for_complete_flush(&logger.read().unwrap());
         start.elapsed()
@@ -149,6 +164,7 @@ fn test_multithread_log_spam() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/4"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -157,31 +173,39 @@ fn test_multithread_log_spam() -> Result<()> {
 
     let output = {
         let start = Instant::now();
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Debug,
-            tag: None,
-            message: "Spam logging now!".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
+                level: LogLevel::Debug,
+                tag: None,
+                message: "Spam logging now!".to_owned(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            })
+            .unwrap();
         let mut handles = vec![];
         for _ in 0..4 {
             let logger = Arc::clone(&logger);
             handles.push(thread::spawn(move || {
                 for i in 0..100000 {
-                    logger.read().unwrap().queue_log(LogData {
-                        level: LogLevel::Debug,
-                        tag: None,
-                        message: format!("log i {i}"),
-                        file: file!().to_string(),
-                        line: line!(),
-                        column: column!(),
-                        function_name: None,
-                        ..Default::default()
-                    });
+                    logger
+                        .read()
+                        .unwrap()
+                        .queue_log(LogData {
+                            level: LogLevel::Debug,
+                            tag: None,
+                            message: format!("log i {i}"),
+                            file: file!().into(),
+                            line: line!(),
+                            column: column!(),
+                            function_name: None,
+                            ..Default::default()
+                        })
+                        .unwrap();
                 }
             }));
         }
@@ -205,6 +229,7 @@ fn test_log_context_output() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/5"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -212,16 +237,20 @@ fn test_log_context_output() -> Result<()> {
     thread::sleep(Duration::from_millis(2));
 
     let output = {
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Info,
-            tag: Some("Context".to_string()),
-            message: "context hi! 6".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: Some("Context".into()),
+                message: "context hi! 6".to_owned(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            })
+            .unwrap();
         wait_for_complete_flush(&logger.read().unwrap());
     };
 
@@ -238,6 +267,7 @@ fn test_log_context_tag_output() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/6"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -246,16 +276,20 @@ fn test_log_context_tag_output() -> Result<()> {
 
     let context = "Context";
     let output = {
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Info,
-            tag: Some(context.to_string()),
-            message: "hi this is a context log! 5".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: Some(context.into()),
+                message: "hi this is a context log! 5".to_owned(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            })
+            .unwrap();
         wait_for_complete_flush(&logger.read().unwrap());
         thread::sleep(Duration::from_millis(2));
     };
@@ -273,6 +307,7 @@ fn test_utf8() -> Result<()> {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/7"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -280,16 +315,20 @@ fn test_utf8() -> Result<()> {
     thread::sleep(Duration::from_millis(2));
 
     let output = {
-        logger.read().unwrap().queue_log(LogData {
-            level: LogLevel::Info,
-            tag: None,
-            message: "£ ह € 한".to_owned(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            function_name: None,
-            ..Default::default()
-        });
+        logger
+            .read()
+            .unwrap()
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: "£ ह € 한".to_owned(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                function_name: None,
+                ..Default::default()
+            })
+            .unwrap();
         wait_for_complete_flush(&logger.read().unwrap());
     };
 
@@ -297,6 +336,169 @@ fn test_utf8() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_json_output() -> Result<()> {
+    let log = LogData {
+        level: LogLevel::Warn,
+        tag: Some("Context".into()),
+        message: "quote \" backslash \\ newline \n done".to_owned(),
+        file: "src\\main.rs".into(),
+        line: 12,
+        column: 4,
+        function_name: None,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    log.write_json_to_io(&mut output)?;
+    let output = String::from_utf8(output)?;
+
+    assert!(output.starts_with("{\"level\":\"WARN\",\"timestamp\":\""));
+    assert!(output.ends_with(
+        "\"tag\":\"Context\",\"file\":\"src\\\\main.rs\",\"line\":12,\"column\":4,\
+         \"function_name\":null,\"message\":\"quote \\\" backslash \\\\ newline \\n done\",\"fields\":{}}\n"
+    ));
+    assert_eq!(output.lines().count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_fields_output() -> Result<()> {
+    let log = LogData {
+        level: LogLevel::Info,
+        message: "request done".to_owned(),
+        fields: vec![
+            ("user_id".to_string(), "42".to_string()),
+            ("request".to_string(), "abc".to_string()),
+        ],
+        ..Default::default()
+    };
+
+    assert!(log.format().ends_with("request done user_id=42 request=abc\n"));
+
+    let mut output = Vec::new();
+    log.write_json_to_io(&mut output)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.ends_with("\"fields\":{\"user_id\":\"42\",\"request\":\"abc\"}}\n"));
+    Ok(())
+}
+
+#[test]
+fn test_field_values() {
+    use crate::FieldValue;
+
+    assert_eq!(FieldValue::from("abc").to_string(), "abc");
+    assert_eq!(FieldValue::Int(-3).to_string(), "-3");
+    assert_eq!(FieldValue::Bytes(512).to_string(), "512 B");
+    assert_eq!(FieldValue::Bytes(1536 * 1024).to_string(), "1.5 MiB");
+    assert_eq!(FieldValue::Duration(Duration::from_nanos(250)).to_string(), "250ns");
+    assert_eq!(FieldValue::Duration(Duration::from_micros(12)).to_string(), "12µs");
+    assert_eq!(FieldValue::Duration(Duration::from_millis(340)).to_string(), "340ms");
+    assert_eq!(FieldValue::Duration(Duration::from_millis(1500)).to_string(), "1.5s");
+
+    let size: String = FieldValue::Bytes(2048).into();
+    assert_eq!(size, "2.0 KiB");
+}
+
+#[test]
+fn test_log_reader_round_trip() -> Result<()> {
+    use crate::LogReader;
+
+    let logs = [
+        LogData {
+            level: LogLevel::Warn,
+            tag: Some("Net".into()),
+            message: "connection slow".to_owned(),
+            file: "src/net.rs".into(),
+            line: 12,
+            column: 5,
+            function_name: Some("net::connect".to_owned()),
+            thread_name: Some("worker-2".to_owned()),
+            thread_id: Some(thread::current().id()),
+            ..Default::default()
+        },
+        LogData {
+            level: LogLevel::Error,
+            message: "first line\nsecond line".to_owned(),
+            chunk_index: 1,
+            chunk_total: 3,
+            ..Default::default()
+        },
+    ];
+
+    let mut output = Vec::new();
+    for log in &logs {
+        log.write_to_io(&mut output)?;
+    }
+
+    let read = LogReader::new(output.as_slice()).collect::<Result<Vec<_>>>()?;
+    assert_eq!(read.len(), 2);
+
+    for (log, read) in logs.iter().zip(&read) {
+        assert_eq!(read.level, log.level);
+        assert_eq!(read.tag, log.tag);
+        assert_eq!(read.message, log.message);
+        assert_eq!(read.file, log.file);
+        assert_eq!(read.line, log.line);
+        assert_eq!(read.column, log.column);
+        assert_eq!(read.function_name, log.function_name);
+        assert_eq!(read.thread_name, log.thread_name);
+        assert_eq!(read.chunk_index, log.chunk_index);
+        assert_eq!(read.chunk_total, log.chunk_total);
+        assert_eq!(read.timestamp.timestamp_millis(), log.timestamp.timestamp_millis());
+    }
+
+    assert!(LogReader::new("no log here\n".as_bytes()).next().unwrap().is_err());
+    Ok(())
+}
+
+#[cfg(feature = "stdout")]
+#[test]
+fn test_level_colors() -> Result<()> {
+    use crate::{DefaultFormatter, LevelColors, LogFormatter};
+    use owo_colors::Style;
+
+    let log = LogData {
+        level: LogLevel::Error,
+        message: "colored".to_owned(),
+        ..Default::default()
+    };
+
+    let format_colored = |level_colors: LevelColors| -> Result<String> {
+        let formatter = DefaultFormatter {
+            level_colors,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        formatter.format_colored(&log, &mut output)?;
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert!(format_colored(LevelColors::default())?.starts_with("\x1b[31mERROR"));
+    let blue = LevelColors {
+        error: Style::new().blue(),
+        ..Default::default()
+    };
+    assert!(format_colored(blue)?.starts_with("\x1b[34mERROR"));
+    Ok(())
+}
+
+#[cfg(feature = "stdout")]
+#[test]
+fn test_color_env_override() {
+    use crate::logger::stdout_logger::color_override;
+    use std::ffi::OsStr;
+
+    let set = |value: &'static str| Some(OsStr::new(value));
+
+    assert_eq!(color_override(None, None, None), None);
+    assert_eq!(color_override(set("1"), set("1"), None), Some(false));
+    assert_eq!(color_override(set(""), set("1"), None), Some(true));
+    assert_eq!(color_override(None, set("0"), None), None);
+    assert_eq!(color_override(None, None, set("0")), Some(false));
+    assert_eq!(color_override(None, None, set("1")), None);
+}
+
 // #[test]
 // fn test_utf16_to_utf8() -> Result<()> {
 //     let config = LoggerConfig {
@@ -304,6 +506,7 @@ fn test_utf8() -> Result<()> {
 //         log_max_buffer_count: 50,
 //         line_end: '\n',
 //         context_log_path: PathBuf::from("./logs/"),
+//         ..Default::default()
 //     };
 //     let log_path = PathBuf::from("./logs/test_log.log");
 
@@ -328,3 +531,278 @@ fn test_utf8() -> Result<()> {
 //     );
 //     Ok(())
 // }
+
+#[test]
+fn test_timestamp_precision() {
+    let log = LogData {
+        message: "precise".to_owned(),
+        ..Default::default()
+    };
+
+    // "INFO [2024-01-01 00:00:00.000] ..."
+    let timestamp = log.format().split(['[', ']']).nth(1).unwrap().to_string();
+    let (_, millis) = timestamp.rsplit_once('.').unwrap();
+    assert_eq!(millis.len(), 3);
+
+    let micros = log.format_with("%H:%M:%S%.6f", false);
+    let (_, micros) = micros.split(['[', ']']).nth(1).unwrap().rsplit_once('.').unwrap();
+    assert_eq!(micros.len(), 6);
+}
+
+#[test]
+fn test_chunk_marker() -> Result<()> {
+    let log = LogData {
+        message: "second part".to_owned(),
+        chunk_index: 1,
+        chunk_total: 3,
+        ..Default::default()
+    };
+    assert!(log.format().ends_with("@default [2/3] second part\n"));
+
+    let mut output = Vec::new();
+    log.write_json_to_io(&mut output)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("\"chunk_index\":1,\"chunk_total\":3"));
+
+    let unsplit = LogData {
+        message: "whole".to_owned(),
+        ..Default::default()
+    };
+    assert!(unsplit.format().ends_with("@default whole\n"));
+    Ok(())
+}
+
+#[test]
+fn test_thread_capture() -> Result<()> {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/28"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger = LoggerThread::new(config, log_path)?;
+    let (thread_id, logger) = thread::Builder::new()
+        .name("worker-2".to_string())
+        .spawn(move || {
+            logger
+                .queue_log(LogData {
+                    message: "from worker".to_owned(),
+                    ..Default::default()
+                })
+                .unwrap();
+            (thread::current().id(), logger)
+        })?
+        .join()
+        .unwrap();
+
+    let queue = logger.get_queue().lock().unwrap();
+    assert_eq!(queue[0].thread_id, Some(thread_id));
+    assert_eq!(queue[0].thread_name.as_deref(), Some("worker-2"));
+    assert!(queue[0]
+        .format()
+        .contains(&format!("[tid={thread_id:?} name=worker-2] ")));
+    Ok(())
+}
+
+#[test]
+fn test_log_macros() {
+    assert!(crate::__function_name!().ends_with("::test_log_macros"));
+
+    // without a global logger these are no-ops, but they have to expand in both forms
+    crate::log_info!(tag: "net", "connected to {}", "127.0.0.1");
+    crate::log_error!("failed after {} attempts", 3);
+
+    // nor are their arguments evaluated
+    let evaluated = std::cell::Cell::new(false);
+    let expensive = || {
+        evaluated.set(true);
+        "expensive"
+    };
+    assert!(!crate::is_level_enabled(LogLevel::Error));
+    crate::log_debug!("{}", expensive());
+    assert!(!evaluated.get());
+}
+
+#[test]
+fn test_default_tag() -> Result<()> {
+    use crate::{DefaultFormatter, LogFormatter};
+
+    let log = LogData {
+        message: "untagged".to_owned(),
+        ..Default::default()
+    };
+    assert!(log.format().contains("] [GLOBAL] "));
+
+    let format = |default_tag: Option<&str>| -> Result<String> {
+        let formatter = DefaultFormatter {
+            default_tag: default_tag.map(str::to_string),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        formatter.format(&log, &mut output)?;
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert!(format(Some("my-binary"))?.contains("] [my-binary] "));
+    let without_tag = format(None)?;
+    assert!(!without_tag.contains("GLOBAL"));
+    assert!(without_tag.contains("] :0:0@default untagged"));
+    Ok(())
+}
+
+#[test]
+fn test_hide_source_location() -> Result<()> {
+    use crate::{DefaultFormatter, LogFormatter};
+
+    let log = LogData {
+        tag: Some("Tag".into()),
+        message: "no location".to_owned(),
+        file: "src/main.rs".into(),
+        line: 12,
+        column: 5,
+        function_name: Some("main".to_owned()),
+        ..Default::default()
+    };
+
+    let format = |show_source_location: bool| -> Result<String> {
+        let formatter = DefaultFormatter {
+            show_source_location,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        formatter.format(&log, &mut output)?;
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert!(format(true)?.ends_with("] [Tag] src/main.rs:12:5@main no location\n"));
+    assert!(format(false)?.ends_with("] [Tag] no location\n"));
+    Ok(())
+}
+
+#[test]
+fn test_include_pid() -> Result<()> {
+    use crate::{LogFormat, LogReader};
+
+    let config = LoggerConfig {
+        include_pid: true,
+        context_log_path: PathBuf::from("./logs/70"),
+        ..Default::default()
+    };
+    let log = LogData {
+        message: "from this process".to_owned(),
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    LogFormat::Text.write(&log, &config, &mut output)?;
+    let line = String::from_utf8(output)?;
+    assert!(line.starts_with(&format!("pid={} INFO [", std::process::id())));
+
+    // the logger formats with what it built at init, not per line
+    let log_path = config.context_log_path.join("test_log.log");
+    let logger = LoggerThread::new(config, log_path)?;
+    let mut output = Vec::new();
+    logger.write_log(&log, &mut output)?;
+    assert_eq!(String::from_utf8(output)?, line);
+
+    let read = LogReader::new(line.as_bytes()).next().unwrap()?;
+    assert_eq!(read.level, LogLevel::Info);
+    assert_eq!(read.message, "from this process");
+    Ok(())
+}
+
+#[test]
+fn test_log_level_u8() {
+    for level in [LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error] {
+        assert_eq!(LogLevel::from_u8(level.as_u8()), level);
+    }
+    assert!(LogLevel::Warn.as_u8() > LogLevel::Info.as_u8());
+    assert!(LogLevel::Warn > LogLevel::Info);
+    assert_eq!(LogLevel::from_u8(5), LogLevel::Error);
+}
+
+#[test]
+fn test_line_template() -> Result<()> {
+    use crate::LogFormat;
+
+    let config = LoggerConfig {
+        line_template: Some("{level} {tag}: {message}{fields} pid={pid} {{{line}}}".to_owned()),
+        ..Default::default()
+    };
+    let log = LogData {
+        message: "templated".to_owned(),
+        fields: vec![("user".to_owned(), "42".to_owned())],
+        line: 7,
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    LogFormat::Text.write(&log, &config, &mut output)?;
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!("INFO GLOBAL: templated user=42 pid={} {{7}}\n", std::process::id())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_write_latency_marker() -> Result<()> {
+    use crate::{LogFormat, LogReader};
+
+    let enqueued_at = chrono::Utc::now();
+    let log = LogData {
+        message: "slow writer".to_owned(),
+        enqueued_at: Some(enqueued_at),
+        written_at: Some(enqueued_at + chrono::TimeDelta::microseconds(5250)),
+        ..Default::default()
+    };
+    assert_eq!(log.write_latency(), Some(Duration::from_micros(5250)));
+
+    let mut output = Vec::new();
+    LogFormat::Text.write(&log, &LoggerConfig::default(), &mut output)?;
+    let line = String::from_utf8(output)?;
+    assert!(line.contains("] +5.250ms "));
+
+    let read = LogReader::new(line.as_bytes()).next().unwrap()?;
+    assert_eq!(read.message, "slow writer");
+    assert_eq!(read.tag, None);
+    Ok(())
+}
+
+#[test]
+fn test_align_columns() -> Result<()> {
+    use crate::{LogFormat, LogFormatter, LogReader};
+
+    let config = LoggerConfig {
+        align_columns: true,
+        tag_width: 8,
+        show_source_location: false,
+        ..Default::default()
+    };
+
+    let formatter = LogFormat::Text.formatter(&config);
+    let mut output = Vec::new();
+    for (level, tag) in [(LogLevel::Info, "Net"), (LogLevel::Error, "Renderer")] {
+        let log = LogData {
+            level,
+            tag: Some(tag.into()),
+            message: "aligned".to_owned(),
+            ..Default::default()
+        };
+        formatter.format(&log, &mut output)?;
+    }
+    let text = String::from_utf8(output)?;
+    let lines: Vec<_> = text.lines().collect();
+    assert!(lines[0].starts_with("INFO  ["));
+    assert!(lines[0].ends_with("] [Net]      aligned"));
+    assert!(lines[1].ends_with("] [Renderer] aligned"));
+
+    let logs = LogReader::new(text.as_bytes()).collect::<Result<Vec<_>>>()?;
+    assert_eq!(logs[0].level, LogLevel::Info);
+    assert_eq!(logs[0].tag.as_deref(), Some("Net"));
+    assert_eq!(logs[0].message, "aligned");
+    Ok(())
+}
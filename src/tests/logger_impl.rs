@@ -14,6 +14,7 @@ fn test_logger_thread_initialization() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/9"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -28,6 +29,7 @@ fn test_logger_thread_init() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: PathBuf::from("./logs/8"),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -45,19 +47,22 @@ fn test_queue_log() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: "./logs".into(),
+        ..Default::default()
     };
     let log_path = PathBuf::from("./logs/test_log.log");
 
     let logger_thread = LoggerThread::new(config, log_path).unwrap();
-    logger_thread.queue_log(LogData {
-        level: LogLevel::Info,
-        tag: Some("test".to_string()),
-        message: "This is a test log".to_string(),
-        file: file!().to_string(),
-        line: line!(),
-        column: column!(),
-        ..Default::default()
-    });
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            tag: Some("test".into()),
+            message: "This is a test log".to_string(),
+            file: file!().into(),
+            line: line!(),
+            column: column!(),
+            ..Default::default()
+        })
+        .unwrap();
 
     {
         let queue = logger_thread.get_queue();
@@ -89,6 +94,7 @@ fn test_queue_log() {
 //         log_max_buffer_count: 50,
 //         line_end: '\n',
 //         context_log_path: "./logs".into(),
+//         ..Default::default()
 //     };
 //     let log_path = PathBuf::from("./logs/test_log.log");
 
@@ -108,6 +114,7 @@ fn test_log_thread() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: "./logs".into(),
+        ..Default::default()
     };
     let log_path = PathBuf::from("./logs/test_log.log");
 
@@ -118,15 +125,17 @@ fn test_log_thread() {
 
     thread::spawn(move || {
         let logger_thread = logger_thread_clone.read().unwrap();
-        logger_thread.queue_log(LogData {
-            level: LogLevel::Info,
-            tag: Some("test".to_string()),
-            message: "This is a test log".to_string(),
-            file: file!().to_string(),
-            line: line!(),
-            column: column!(),
-            ..Default::default()
-        });
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: Some("test".into()),
+                message: "This is a test log".to_string(),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                ..Default::default()
+            })
+            .unwrap();
     })
     .join()
     .unwrap();
@@ -136,3 +145,2166 @@ fn test_log_thread() {
     let queue = logger_thread.get_queue().lock().unwrap().len();
     // assert_eq!(queue, 0);
 }
+
+#[test]
+fn test_shutdown_flushes_queue() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/11"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..1000 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: format!("shutdown log {i}"),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    assert!(logger_thread.is_shutdown());
+    assert!(logger_thread.get_queue().lock().unwrap().is_empty());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("shutdown log 999"));
+}
+
+#[test]
+fn test_file_rotation() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/12"),
+        rotation: crate::RotationPolicy::Size(1024),
+        max_files: 2,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+    let _ = std::fs::remove_file(config.context_log_path.join("test_log.3.log"));
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..1000 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: format!("rotated log {i}"),
+                file: file!().into(),
+                line: line!(),
+                column: column!(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    assert!(std::fs::metadata(&log_path).unwrap().len() <= 2048);
+    assert!(log_path.with_file_name("test_log.1.log").exists());
+    assert!(log_path.with_file_name("test_log.2.log").exists());
+    assert!(!log_path.with_file_name("test_log.3.log").exists());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("rotated log 999"));
+}
+
+#[test]
+fn test_daily_rotation() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/44"),
+        rotation: crate::RotationPolicy::Daily,
+        append: true,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // a log left behind by a run two days ago
+    let two_days_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+    std::fs::create_dir_all(&config.context_log_path).unwrap();
+    std::fs::write(&log_path, "old log\n").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&log_path)
+        .unwrap()
+        .set_modified(two_days_ago)
+        .unwrap();
+    let old_day = chrono::DateTime::<chrono::Utc>::from(two_days_ago).format("%Y-%m-%d");
+    let rotated_path = config.context_log_path.join(format!("test_log-{old_day}.log"));
+    let _ = std::fs::remove_file(&rotated_path);
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "new log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    logger_thread.shutdown().unwrap();
+
+    assert_eq!(std::fs::read_to_string(rotated_path).unwrap(), "old log\n");
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(!contents.contains("old log"));
+    assert!(contents.contains("new log"));
+}
+
+#[test]
+fn test_daily_rotation_ignores_sibling_contexts() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/68"),
+        rotation: crate::RotationPolicy::Daily,
+        max_files: 1,
+        append: true,
+        ..Default::default()
+    };
+    let dir = config.context_log_path.clone();
+    let log_path = dir.join("test_log.log");
+
+    let days_ago = |days: u64| std::time::SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+    let day = |time: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d");
+    std::fs::create_dir_all(&dir).unwrap();
+    // `x` is due a rotation and already has an older period, which is past `max_files`
+    std::fs::write(dir.join("x.log"), "old x log\n").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("x.log"))
+        .unwrap()
+        .set_modified(days_ago(2))
+        .unwrap();
+    let oldest_period = dir.join(format!("x-{}.log", day(days_ago(3))));
+    std::fs::write(&oldest_period, "oldest x log\n").unwrap();
+    // the live file of context `x-1` looks like a period of `x` to a loose match
+    std::fs::write(dir.join("x-1.log"), "x-1 log\n").unwrap();
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_context("x").unwrap();
+    logger_thread.add_context("x-1").unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            tag: Some("x".into()),
+            message: "new x log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    logger_thread.shutdown().unwrap();
+
+    assert!(!oldest_period.exists());
+    let rotated = dir.join(format!("x-{}.log", day(days_ago(2))));
+    assert_eq!(std::fs::read_to_string(rotated).unwrap(), "old x log\n");
+    assert_eq!(std::fs::read_to_string(dir.join("x-1.log")).unwrap(), "x-1 log\n");
+}
+
+#[test]
+fn test_min_level_filters_queue() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/13"),
+        min_level: LogLevel::Info,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so nothing drains the queue
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+
+    let log = |level| LogData {
+        level,
+        message: "filtered".to_string(),
+        ..Default::default()
+    };
+
+    logger_thread.queue_log(log(LogLevel::Debug)).unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 0);
+
+    logger_thread.queue_log(log(LogLevel::Info)).unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 1);
+
+    logger_thread.set_min_level(LogLevel::Error);
+    assert_eq!(logger_thread.min_level(), LogLevel::Error);
+
+    logger_thread.queue_log(log(LogLevel::Warn)).unwrap();
+    logger_thread.queue_log(log(LogLevel::Error)).unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 2);
+
+    logger_thread.set_enabled(false);
+    assert!(!logger_thread.is_level_enabled(LogLevel::Error));
+    logger_thread.queue_log(log(LogLevel::Error)).unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 2);
+
+    logger_thread.set_enabled(true);
+    logger_thread.queue_log(log(LogLevel::Error)).unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_add_remove_context() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/14"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_context("Connection2").unwrap();
+    logger_thread.add_context("Connection1").unwrap();
+    assert_eq!(logger_thread.contexts(), vec!["Connection1", "Connection2"]);
+
+    assert!(logger_thread.add_context("Connection1").is_err());
+    assert!(!logger_thread.add_or_get_context("Connection1").unwrap());
+    assert!(logger_thread.add_or_get_context("Connection3").unwrap());
+    logger_thread.remove_context("Connection3").unwrap();
+
+    logger_thread.remove_context("Connection1").unwrap();
+    assert_eq!(logger_thread.contexts(), vec!["Connection2"]);
+
+    assert!(logger_thread.remove_context("Connection1").is_err());
+}
+
+#[test]
+fn test_named_sinks() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/15"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_named_sink("debug", |_: &LogData| -> crate::Result<()> { Ok(()) });
+    logger_thread.add_named_sink("debug", |_: &LogData| -> crate::Result<()> { Ok(()) });
+    assert_eq!(logger_thread.get_named_sinks().len(), 1);
+
+    assert!(logger_thread.remove_sink("debug"));
+    assert!(!logger_thread.remove_sink("debug"));
+    assert!(logger_thread.get_named_sinks().is_empty());
+}
+
+#[test]
+fn test_context_routing() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/17"),
+        ..Default::default()
+    };
+    let context_path = config.context_log_path.join("Routed.log");
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.add_context("Routed").unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for (tag, message) in [
+        (Some("Routed"), "routed log"),
+        (Some("Unknown"), "unknown log"),
+        (None, "global log"),
+    ] {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: tag.map(Into::into),
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    let global = std::fs::read_to_string(log_path).unwrap();
+    assert!(global.contains("routed log"));
+    assert!(global.contains("unknown log"));
+    assert!(global.contains("global log"));
+
+    let context = std::fs::read_to_string(context_path).unwrap();
+    assert!(context.contains("routed log"));
+    assert!(!context.contains("unknown log"));
+    assert!(!context.contains("global log"));
+}
+
+#[test]
+fn test_context_formatter() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/39"),
+        ..Default::default()
+    };
+    let json_path = config.context_log_path.join("Json.log");
+    let text_path = config.context_log_path.join("Text.log");
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread
+        .add_context_with_formatter("Json", Arc::new(crate::JsonFormatter))
+        .unwrap();
+    logger_thread.add_context("Text").unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for tag in ["Json", "Text"] {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: Some(tag.into()),
+                message: format!("{tag} log"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    let json = std::fs::read_to_string(json_path).unwrap();
+    assert!(json.starts_with("{\"level\":\"INFO\""));
+    assert!(json.contains("\"message\":\"Json log\""));
+
+    let text = std::fs::read_to_string(text_path).unwrap();
+    assert!(text.starts_with("INFO ["));
+    assert!(text.contains("Text log"));
+
+    // the global file keeps its own layout for both
+    let global = std::fs::read_to_string(log_path).unwrap();
+    assert_eq!(global.lines().filter(|line| line.starts_with("INFO [")).count(), 2);
+}
+
+#[test]
+fn test_log_sync() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/40"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .log_sync(LogData {
+            level: LogLevel::Error,
+            message: "last words".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // on disk without waiting for the writer thread
+    let global = std::fs::read_to_string(&log_path).unwrap();
+    assert!(global.contains("last words"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_layer() {
+    use std::sync::RwLock;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/42"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // without a writer thread the logs stay in the queue
+    let logger_thread = Arc::new(RwLock::new(LoggerThread::new(config, log_path).unwrap()));
+
+    let subscriber = tracing_subscriber::registry().with(crate::PaperlogLayer::new(logger_thread.clone()));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", id = 7);
+        let _entered = span.enter();
+        tracing::warn!(target: "net", retries = 3, "connection slow");
+    });
+
+    let logger_thread = logger_thread.read().unwrap();
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.len(), 1);
+
+    let log = &queue[0];
+    assert_eq!(log.level, LogLevel::Warn);
+    assert_eq!(log.tag.as_deref(), Some("net"));
+    assert_eq!(log.message, "connection slow");
+    let fields =
+        [("spans", "request"), ("id", "7"), ("retries", "3")].map(|(key, value)| (key.to_string(), value.to_string()));
+    assert_eq!(log.fields, fields);
+}
+
+#[test]
+fn test_poisoned_lock_recovery() {
+    use crate::logger::read_logger;
+    use std::sync::RwLock;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/43"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = Arc::new(RwLock::new(LoggerThread::new(config, log_path).unwrap()));
+
+    let poisoner = logger_thread.clone();
+    let _ = thread::spawn(move || {
+        let _logger_thread = poisoner.write().unwrap();
+        panic!("poisoning the logger");
+    })
+    .join();
+    assert!(logger_thread.is_poisoned());
+
+    for _ in 0..2 {
+        read_logger(&logger_thread)
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: "after poison".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // warned about only once
+    let logger_thread = read_logger(&logger_thread);
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.len(), 3);
+    assert_eq!(queue[0].level, LogLevel::Warn);
+    assert!(queue[0].message.contains("poisoned"));
+}
+
+#[test]
+fn test_alert_hook() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/47"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = std::sync::Mutex::new(sender);
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.set_alert_hook(LogLevel::Error, move |log: &LogData| {
+        let _ = sender.lock().unwrap().send(log.message.clone());
+    });
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for (level, message) in [(LogLevel::Warn, "just a warning"), (LogLevel::Error, "page someone")] {
+        logger_thread
+            .queue_log(LogData {
+                level,
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), ["page someone"]);
+}
+
+#[test]
+fn test_log_seq() {
+    use crate::LogFormatter;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 3,
+        overflow_policy: crate::OverflowPolicy::DropNewest,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/48"),
+        show_seq: true,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so the fourth log overflows the queue
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    for i in 0..5 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.iter().map(|log| log.seq).collect::<Vec<_>>(), [1, 2, 3]);
+
+    let mut line = Vec::new();
+    logger_thread.formatter().format(&queue[1], &mut line).unwrap();
+    assert!(String::from_utf8(line).unwrap().contains("] #2 [GLOBAL] "));
+    drop(queue);
+
+    // the dropped logs still used up their numbers
+    logger_thread.get_queue().lock().unwrap().clear();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "after the gap".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(logger_thread.get_queue().lock().unwrap()[0].seq, 6);
+}
+
+#[test]
+fn test_queue_overflow_policy() {
+    use crate::OverflowPolicy;
+
+    let log = |i| LogData {
+        level: LogLevel::Info,
+        message: format!("overflow log {i}"),
+        ..Default::default()
+    };
+
+    for (policy, expected_first) in [
+        (OverflowPolicy::DropOldest, "overflow log 5"),
+        (OverflowPolicy::DropNewest, "overflow log 0"),
+    ] {
+        let config = LoggerConfig {
+            max_string_len: 100,
+            log_max_buffer_count: 5,
+            overflow_policy: policy,
+            line_end: '\n',
+            context_log_path: PathBuf::from("./logs/18"),
+            ..Default::default()
+        };
+        let log_path = config.context_log_path.join("test_log.log");
+
+        // not inited, so nothing drains the queue
+        let logger_thread = LoggerThread::new(config, log_path).unwrap();
+        for i in 0..10 {
+            logger_thread.queue_log(log(i)).unwrap();
+        }
+
+        let queue = logger_thread.get_queue().lock().unwrap();
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue[0].message, expected_first);
+        assert_eq!(logger_thread.dropped_log_count(), 5);
+    }
+}
+
+#[test]
+fn test_drop_oldest_one_per_overflow() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 16,
+        overflow_policy: crate::OverflowPolicy::DropOldest,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/71"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so nothing drains the queue
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    for i in 0..17 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("overflow log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // only the oldest log makes room, however long the queue
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.len(), 16);
+    assert_eq!(queue[0].message, "overflow log 1");
+    assert_eq!(logger_thread.dropped_log_count(), 1);
+}
+
+#[test]
+fn test_guaranteed_tag() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 3,
+        overflow_policy: crate::OverflowPolicy::DropOldest,
+        min_level: LogLevel::Warn,
+        sample_rate: [(LogLevel::Info, 0.0)].into(),
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/58"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so nothing drains the queue
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.set_guaranteed_tag("audit");
+    logger_thread.set_rate_limit("audit", 1);
+    for i in 0..5 {
+        for tag in ["audit", "noise"] {
+            logger_thread
+                .queue_log(LogData {
+                    level: LogLevel::Info,
+                    tag: Some(tag.into()),
+                    message: format!("{tag} {i}"),
+                    ..Default::default()
+                })
+                .unwrap();
+            logger_thread
+                .queue_log(LogData {
+                    level: LogLevel::Warn,
+                    tag: Some("noise".into()),
+                    message: format!("warn {i}"),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+    }
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    let audit: Vec<_> = queue
+        .iter()
+        .filter(|log| log.tag.as_deref() == Some("audit"))
+        .map(|log| log.message.as_str())
+        .collect();
+    assert_eq!(audit, ["audit 0", "audit 1", "audit 2", "audit 3", "audit 4"]);
+    // ordinary logs still make room for each other, but never for the audit ones
+    let others: Vec<_> = queue
+        .iter()
+        .filter(|log| log.tag.as_deref() != Some("audit"))
+        .map(|log| log.message.as_str())
+        .collect();
+    assert_eq!(others, ["warn 4", "warn 4"]);
+    drop(queue);
+
+    logger_thread.set_enabled(false);
+    logger_thread
+        .queue_log(LogData {
+            tag: Some("audit".into()),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(logger_thread.queue_depth(), 7);
+}
+
+#[test]
+fn test_max_queue_age() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        max_queue_age: Some(Duration::from_millis(50)),
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/60"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited yet, standing in for a stalled writer thread
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.set_guaranteed_tag("audit");
+    let log = |logger_thread: &LoggerThread, tag: &str, message: &str| {
+        logger_thread
+            .queue_log(LogData {
+                tag: Some(tag.to_string().into()),
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    };
+    for i in 0..3 {
+        log(&logger_thread, "stale", &format!("stale {i}"));
+    }
+    log(&logger_thread, "audit", "old audit");
+    thread::sleep(Duration::from_millis(100));
+    log(&logger_thread, "fresh", "fresh");
+
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    let logger_thread = thread_safe_logger.read().unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(&log_path).unwrap();
+    assert!(!contents.contains("stale"));
+    assert!(contents.contains("old audit"));
+    assert!(contents.contains("fresh"));
+    assert_eq!(logger_thread.expired_log_count(), 3);
+    assert_eq!(logger_thread.total_logged(), 2);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_log_error_report() {
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/61"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    let report = Err::<(), _>(eyre!("disk full\nwhile syncing"))
+        .wrap_err("Unable to write save")
+        .wrap_err("Unable to quit")
+        .unwrap_err();
+    logger_thread.log_error_report(&report, Some("saves")).unwrap();
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].level, LogLevel::Error);
+    assert_eq!(queue[0].tag.as_deref(), Some("saves"));
+    assert_eq!(
+        queue[0].message,
+        "Unable to quit\n  caused by: Unable to write save\n  caused by: disk full\n    while syncing"
+    );
+    assert_eq!(queue[0].file, file!());
+}
+
+#[test]
+fn test_flush_writes_to_file() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/19"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "flushed log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("flushed log"));
+    assert_eq!(logger_thread.total_logged(), 1);
+    assert_eq!(logger_thread.queue_depth(), 0);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_wait_until_idle() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/67"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    assert!(logger_thread.wait_until_idle(None));
+    logger_thread
+        .queue_log(LogData {
+            message: "never drained".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    // nothing would ever drain it
+    assert!(!logger_thread.wait_until_idle(None));
+    logger_thread.get_queue().lock().unwrap().clear();
+
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    let producer = {
+        let thread_safe_logger = thread_safe_logger.clone();
+        thread::spawn(move || {
+            for i in 0..200 {
+                thread_safe_logger
+                    .read()
+                    .unwrap()
+                    .queue_log(LogData {
+                        message: format!("idle log {i}"),
+                        ..Default::default()
+                    })
+                    .unwrap();
+            }
+        })
+    };
+    producer.join().unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    assert!(logger_thread.wait_until_idle(Some(Duration::from_secs(5))));
+    assert_eq!(logger_thread.queue_depth(), 0);
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("idle log 199"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_flush_under_steady_load() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/51"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let spammer = {
+        let logger = thread_safe_logger.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let _ = logger.read().unwrap().queue_log(LogData {
+                    message: "background log".to_string(),
+                    ..Default::default()
+                });
+            }
+        })
+    };
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            message: "before flush".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    // completes although the queue keeps refilling
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    assert!(std::fs::read_to_string(&log_path).unwrap().contains("before flush"));
+
+    stop.store(true, Ordering::Relaxed);
+    spammer.join().unwrap();
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_error_log_file() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/52"),
+        error_log_path: Some(PathBuf::from("./logs/52/errors/errors.log")),
+        error_log_level: LogLevel::Warn,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for (level, message) in [
+        (LogLevel::Info, "info log"),
+        (LogLevel::Warn, "warn log"),
+        (LogLevel::Error, "error log"),
+    ] {
+        logger_thread
+            .queue_log(LogData {
+                level,
+                tag: Some("Subsystem".into()),
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let errors = std::fs::read_to_string("./logs/52/errors/errors.log").unwrap();
+    assert_eq!(errors.lines().count(), 2);
+    assert!(errors.contains("warn log") && errors.contains("error log"));
+    assert_eq!(std::fs::read_to_string(log_path).unwrap().lines().count(), 3);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_async_sink() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/20"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread
+        .add_async_sink(move |log: &LogData| -> crate::Result<()> {
+            sender.send(log.message.clone())?;
+            Ok(())
+        })
+        .unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "async sink log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message, "async sink log");
+    assert_eq!(logger_thread.async_sink_dropped_count(), 0);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_async_sink_retry() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/38"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    struct FlakySink {
+        attempts: std::sync::atomic::AtomicU32,
+        sender: std::sync::Mutex<std::sync::mpsc::Sender<(String, u32)>>,
+    }
+
+    impl crate::LogSink for FlakySink {
+        fn send(&self, log: &LogData) -> crate::SinkStatus {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            if log.message == "dropped log" {
+                return crate::SinkStatus::Dropped;
+            }
+            if attempt < 3 {
+                return crate::SinkStatus::Retry;
+            }
+
+            let _ = self.sender.lock().unwrap().send((log.message.clone(), attempt));
+            crate::SinkStatus::Accepted
+        }
+    }
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread
+        .add_async_sink(FlakySink {
+            attempts: Default::default(),
+            sender: std::sync::Mutex::new(sender),
+        })
+        .unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for message in ["retried log", "dropped log"] {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let (message, attempt) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message, "retried log");
+    assert_eq!(attempt, 3);
+
+    for _ in 0..50 {
+        if logger_thread.async_sink_dropped_count() == 1 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(logger_thread.async_sink_dropped_count(), 1);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_dedup_repeated_logs() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/21"),
+        dedup: true,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for message in std::iter::repeat("repeated log").take(100).chain(["distinct log"]) {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: message.to_string(),
+                file: file!().into(),
+                line: line!(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("repeated log"));
+    assert!(lines[1].ends_with("last message repeated 99 times"));
+    assert!(lines[2].ends_with("distinct log"));
+}
+
+#[test]
+fn test_rate_limit_per_tag() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 0,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/22"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so nothing drains the queue
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.set_rate_limit("Noisy", 5);
+
+    for tag in ["Noisy", "Quiet"] {
+        for _ in 0..20 {
+            logger_thread
+                .queue_log(LogData {
+                    level: LogLevel::Info,
+                    tag: Some(tag.into()),
+                    message: "rate limited".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+    }
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    let count = |tag: &str| queue.iter().filter(|log| log.tag.as_deref() == Some(tag)).count();
+    assert_eq!(count("Noisy"), 5);
+    assert_eq!(count("Quiet"), 20);
+    drop(queue);
+
+    assert_eq!(logger_thread.queue_depth(), 25);
+    assert_eq!(logger_thread.total_dropped(), 15);
+}
+
+#[test]
+fn test_restart_dead_writer() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/23"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    // failing sinks no longer stop the writer thread, a panicking one still does
+    logger_thread.add_named_sink("panicking", |log: &LogData| -> crate::Result<()> {
+        match log.message.as_str() {
+            "fail" => panic!("sink failed"),
+            _ => Ok(()),
+        }
+    });
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    assert!(logger_thread.is_writer_alive());
+    assert!(!logger_thread.restart_writer().unwrap());
+
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "fail".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    for _ in 0..100 {
+        if !logger_thread.is_writer_alive() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(!logger_thread.is_writer_alive());
+
+    assert!(logger_thread.restart_writer().unwrap());
+    assert!(logger_thread.is_writer_alive());
+    assert_eq!(logger_thread.last_writer_error().unwrap(), "Logger thread panicked");
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_restart_after_panicking_formatter() {
+    struct PanickingFormatter;
+
+    impl crate::LogFormatter for PanickingFormatter {
+        fn format(&self, _: &LogData, _: &mut dyn std::io::Write) -> std::io::Result<()> {
+            panic!("formatter failed")
+        }
+    }
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/69"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread
+        .add_context_with_formatter("panicking", Arc::new(PanickingFormatter))
+        .unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    // panics with the files locked, poisoning them
+    logger_thread
+        .queue_log(LogData {
+            tag: Some("panicking".into()),
+            message: "fail".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    for _ in 0..100 {
+        if !logger_thread.is_writer_alive() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!logger_thread.is_writer_alive());
+
+    assert!(logger_thread.restart_writer().unwrap());
+    logger_thread
+        .queue_log(LogData {
+            message: "after restart".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    assert!(logger_thread.is_writer_alive());
+    assert!(std::fs::read_to_string(log_path).unwrap().contains("after restart"));
+    drop(logger_thread);
+
+    // the contexts are still usable with the file locks poisoned
+    let mut logger_thread = thread_safe_logger.write().unwrap();
+    assert_eq!(logger_thread.contexts(), ["panicking"]);
+    logger_thread.add_context("after_restart").unwrap();
+    logger_thread.remove_context("panicking").unwrap();
+    assert_eq!(logger_thread.contexts(), ["after_restart"]);
+    drop(logger_thread);
+
+    thread_safe_logger.read().unwrap().shutdown().unwrap();
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compressed_rotation() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/24"),
+        rotation: crate::RotationPolicy::Size(1024),
+        max_files: 2,
+        compress_rotated: true,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..1000 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                tag: None,
+                message: format!("compressed log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.shutdown().unwrap();
+
+    // the newest rotated file may still be compressing
+    assert!(log_path.with_file_name("test_log.2.log.gz").exists());
+    assert!(!log_path.with_file_name("test_log.2.log").exists());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compress_active() {
+    use std::io::Read;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/62"),
+        compress_active: true,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..10 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("gzipped log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    assert!(!log_path.exists());
+    let compressed = std::fs::read(log_path.with_file_name("test_log.log.gz")).unwrap();
+    // the trailer is missing until the file is closed, so the stream ends early
+    let mut contents = Vec::new();
+    let _ = flate2::read::MultiGzDecoder::new(&compressed[..]).read_to_end(&mut contents);
+    let contents = String::from_utf8(contents).unwrap();
+    assert!(contents.contains("gzipped log 0"));
+    assert!(contents.contains("gzipped log 9"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "syslog")]
+#[test]
+fn test_syslog_sink_udp() {
+    use crate::SyslogTransport;
+
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/25"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread
+        .add_syslog_sink(server.local_addr().unwrap(), SyslogTransport::Udp)
+        .unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Warn,
+            tag: Some("my app".into()),
+            message: "syslog log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut buf = [0; 1024];
+    let len = server.recv(&mut buf).unwrap();
+    let message = std::str::from_utf8(&buf[..len]).unwrap();
+
+    // user facility (1) * 8 + warning (4)
+    assert!(message.starts_with("<12>1 "));
+    assert!(message.contains(&format!(" myapp {} - - syslog log", std::process::id())));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_custom_formatter() {
+    struct UpperFormatter;
+
+    impl crate::LogFormatter for UpperFormatter {
+        fn format(&self, data: &LogData, out: &mut dyn std::io::Write) -> std::io::Result<()> {
+            writeln!(out, "CUSTOM {}", data.message.to_uppercase())
+        }
+    }
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/27"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.set_formatter(UpperFormatter);
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "formatted log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("CUSTOM FORMATTED LOG"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_sink_min_level() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/29"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let alerts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink_alerts = alerts.clone();
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_sink_with_level(
+        move |log: &LogData| -> crate::Result<()> {
+            sink_alerts.lock().unwrap().push(log.message.clone());
+            Ok(())
+        },
+        LogLevel::Error,
+    );
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for (level, message) in [(LogLevel::Info, "info log"), (LogLevel::Error, "error log")] {
+        logger_thread
+            .queue_log(LogData {
+                level,
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    assert_eq!(*alerts.lock().unwrap(), vec!["error log".to_string()]);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_sample_rate() {
+    let mut config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 0,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/30"),
+        ..Default::default()
+    };
+    config.sample_rate.insert(LogLevel::Debug, 0.0);
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so nothing drains the queue
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    for level in [LogLevel::Debug, LogLevel::Info] {
+        for _ in 0..10 {
+            logger_thread
+                .queue_log(LogData {
+                    level,
+                    message: "sampled".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+    }
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert!(queue.iter().all(|log| log.level == LogLevel::Info));
+    assert_eq!(queue.len(), 10);
+    assert_eq!(logger_thread.sampled_out_count(LogLevel::Debug), 10);
+    assert_eq!(logger_thread.sampled_out_count(LogLevel::Info), 0);
+
+    let invalid = LoggerConfig::builder().sample_rate(LogLevel::Debug, 1.5).build();
+    assert!(invalid.is_err());
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_crlf_line_ending() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        line_ending: crate::LineEnding::CrLf,
+        context_log_path: PathBuf::from("./logs/31"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "first line\r\nsecond line".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("first line\r\n"));
+    assert!(contents.contains("second line\r\n"));
+    assert!(!contents.contains("\r\r"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_ring_buffer_sink() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/32"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_ring_buffer_sink(3);
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..5 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("ring log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let messages = logger_thread
+        .dump_ring_buffer()
+        .into_iter()
+        .map(|log| log.message)
+        .collect::<Vec<_>>();
+    assert_eq!(messages, ["ring log 2", "ring log 3", "ring log 4"]);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_ring_buffer_keeps_queued_logs() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/73"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so the logs never leave the queue
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_ring_buffer_sink(3);
+    for i in 0..2 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("unwritten log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let messages = logger_thread
+        .dump_ring_buffer()
+        .into_iter()
+        .map(|log| log.message)
+        .collect::<Vec<_>>();
+    assert_eq!(messages, ["unwritten log 0", "unwritten log 1"]);
+    assert_eq!(logger_thread.get_queue().lock().unwrap().len(), 2);
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_install_panic_hook() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/33"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    crate::install_panic_hook(thread_safe_logger.clone());
+
+    let result = thread::spawn(|| panic!("hooked panic")).join();
+    assert!(result.is_err());
+
+    // back to the default hook, so other tests' panics don't end up here
+    let _ = std::panic::take_hook();
+
+    // flushed by the hook itself
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains("panicked at 'hooked panic'"));
+    #[cfg(feature = "backtrace")]
+    assert!(contents.contains("[BACKTRACE]") && !contents.contains("disabled backtrace"));
+
+    thread_safe_logger.read().unwrap().shutdown().unwrap();
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_truncate_long_messages() {
+    let config = LoggerConfig {
+        max_string_len: 10,
+        long_message_policy: crate::LongMessagePolicy::TruncateWithEllipsis,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/34"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "0123456789abcdef\nshort".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains(" 012345678…\n"));
+    assert!(contents.contains(" short\n"));
+    assert!(!contents.contains("abcdef"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_sanitize_control_chars() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        sanitize_control_chars: true,
+        context_log_path: PathBuf::from("./logs/64"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "user=\u{1b}[31mred\u{1b}[0m\tok\rI fake\r\nnext line".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    assert!(contents.contains(" user=\\u{1b}[31mred\\u{1b}[0m\tok\\rI fake\n"));
+    assert!(contents.contains(" next line\n"));
+    assert!(!contents.contains('\u{1b}'));
+    assert!(!contents.contains('\r'));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(all(feature = "tcp", feature = "sinks"))]
+#[test]
+fn test_tcp_sink() {
+    use std::io::BufRead;
+
+    let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/35"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_tcp_sink(server.local_addr().unwrap()).unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "tcp log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let (stream, _) = server.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut line = String::new();
+    std::io::BufReader::new(stream).read_line(&mut line).unwrap();
+
+    assert!(line.starts_with("INFO ["));
+    assert!(line.ends_with(" tcp log\n"));
+    assert_eq!(logger_thread.tcp_sink_dropped_count(), 0);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_set_max_string_len() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/36"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    assert_eq!(logger_thread.max_string_len(), 100);
+
+    logger_thread.set_max_string_len(20).unwrap();
+    assert_eq!(logger_thread.max_string_len(), 20);
+
+    assert!(logger_thread.set_max_string_len(0).is_err());
+    assert_eq!(logger_thread.max_string_len(), 20);
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn test_test_sink() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/49"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let sink = crate::TestSink::new();
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_sink(sink.callback());
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for (level, message) in [(LogLevel::Info, "first log"), (LogLevel::Warn, "second log")] {
+        logger_thread
+            .queue_log(LogData {
+                level,
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    assert_eq!(sink.logged_messages(), ["first log", "second log"]);
+    sink.assert_logged(LogLevel::Warn, "second");
+    assert!(!sink.contains(LogLevel::Error, "second"));
+
+    sink.clear();
+    assert!(sink.logs().is_empty());
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "file")]
+#[test]
+fn test_logger_with_writer() {
+    use std::io::Write;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/50"),
+        ..Default::default()
+    };
+
+    let buffer = SharedBuffer::default();
+    let mut logger_thread = LoggerThread::with_writer(config, buffer.clone()).unwrap();
+    logger_thread.add_context("ignored").unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            tag: Some("ignored".into()),
+            message: "to the writer".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert!(output.starts_with("INFO ["));
+    assert!(output.ends_with(" to the writer\n"));
+    assert!(!PathBuf::from("./logs/50").exists());
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_shutdown_timeout() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/53"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let (entered_sender, entered) = std::sync::mpsc::channel();
+    let (release, release_receiver) = std::sync::mpsc::channel::<()>();
+    let release_receiver = std::sync::Mutex::new(release_receiver);
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_named_sink("stuck", move |_: &LogData| -> crate::Result<()> {
+        let _ = entered_sender.send(());
+        // blocks until the test drops `release`
+        let _ = release_receiver.lock().unwrap().recv();
+        Ok(())
+    });
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            message: "never finishes".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    entered.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    let error = logger_thread.shutdown_timeout(Duration::from_millis(50)).unwrap_err();
+    assert!(error.to_string().contains("sink \"stuck\""));
+
+    drop(release);
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_disabling_keeps_queued_sink_logs() {
+    use std::sync::atomic::AtomicUsize;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/72"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let received = Arc::new(AtomicUsize::new(0));
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    let sink_received = received.clone();
+    logger_thread.add_sink(move |_: &LogData| -> crate::Result<()> {
+        sink_received.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    });
+
+    // accepted before switching off, so they still reach the sinks
+    for i in 0..3 {
+        logger_thread
+            .queue_log(LogData {
+                message: format!("queued log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    logger_thread.set_enabled(false);
+
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    let logger_thread = thread_safe_logger.read().unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    assert_eq!(received.load(Ordering::SeqCst), 3);
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "backtrace")]
+#[test]
+fn test_force_backtrace() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/54"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    // not inited, so the log stays in the queue
+    let logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.force_backtrace(LogLevel::Warn).unwrap();
+
+    let queue = logger_thread.get_queue().lock().unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].level, LogLevel::Warn);
+    assert_eq!(queue[0].tag.as_deref(), Some("BACKTRACE"));
+    assert_ne!(queue[0].message, "disabled backtrace");
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_formatted_sinks_share_line() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/55"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let lines = Arc::new(std::sync::Mutex::new(Vec::<Arc<str>>::new()));
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    for _ in 0..2 {
+        let lines = lines.clone();
+        logger_thread.add_formatted_sink(move |formatted: &crate::FormattedLog| {
+            assert_eq!(formatted.log.message, "formatted log");
+            lines.lock().unwrap().push(formatted.line.clone());
+            Ok(())
+        });
+    }
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "formatted log".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 2);
+    assert!(Arc::ptr_eq(&lines[0], &lines[1]));
+    assert!(lines[0].contains("formatted log"));
+    assert!(lines[0].ends_with('\n'));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_reopen_files() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/59"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+    let context_path = config.context_log_path.join("rotated.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.add_context("rotated").unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    let log = |message: &str| {
+        logger_thread
+            .queue_log(LogData {
+                tag: Some("rotated".into()),
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    };
+
+    log("before rotation");
+    // what logrotate does before sending SIGHUP
+    let moved_log_path = log_path.with_extension("log.moved");
+    let moved_context_path = context_path.with_extension("log.moved");
+    std::fs::rename(&log_path, &moved_log_path).unwrap();
+    std::fs::rename(&context_path, &moved_context_path).unwrap();
+    logger_thread.reopen_files().unwrap();
+    log("after rotation");
+
+    for (path, moved_path) in [(&log_path, &moved_log_path), (&context_path, &moved_context_path)] {
+        let moved = std::fs::read_to_string(moved_path).unwrap();
+        assert!(moved.contains("before rotation"));
+        assert!(!moved.contains("after rotation"));
+        let reopened = std::fs::read_to_string(path).unwrap();
+        assert!(!reopened.contains("before rotation"));
+        assert!(reopened.contains("after rotation"));
+    }
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(all(unix, feature = "signals"))]
+#[test]
+fn test_signal_flush() {
+    use signal_hook::consts::SIGURG;
+
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/56"),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    let not_running = Arc::new(std::sync::RwLock::new(logger_thread));
+    assert!(crate::install_signal_flush(not_running.clone(), &[SIGURG]).is_err());
+
+    let logger_thread = Arc::into_inner(not_running).unwrap().into_inner().unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    crate::install_signal_flush(thread_safe_logger.clone(), &[SIGURG]).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            level: LogLevel::Info,
+            message: "before signal".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    // ignored by default, so the process carries on after the flush
+    signal_hook::low_level::raise(SIGURG).unwrap();
+
+    for _ in 0..100 {
+        if std::fs::read_to_string(&log_path).unwrap().contains("before signal") {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(std::fs::read_to_string(&log_path).unwrap().contains("before signal"));
+    assert!(logger_thread.is_writer_alive());
+
+    logger_thread.shutdown().unwrap();
+    // the handlers went with the writer thread, nothing would act on new ones either
+    assert!(crate::install_signal_flush(thread_safe_logger.clone(), &[SIGURG]).is_err());
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_slow_sink_warning() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/63"),
+        slow_sink_threshold: Some(Duration::from_millis(5)),
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.add_named_sink("slow", |_: &LogData| -> crate::Result<()> {
+        thread::sleep(Duration::from_millis(20));
+        Ok(())
+    });
+    logger_thread.add_named_sink("fast", |_: &LogData| -> crate::Result<()> { Ok(()) });
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..3 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    // the warning is queued by the writer, write it as well
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let file = std::fs::read_to_string(log_path).unwrap();
+    assert_eq!(file.matches("sink \"slow\" took").count(), 1);
+    assert!(!file.contains("sink \"fast\" took"));
+
+    logger_thread.shutdown().unwrap();
+}
+
+#[cfg(feature = "sinks")]
+#[test]
+fn test_failing_sink_is_isolated() {
+    let config = LoggerConfig {
+        max_string_len: 100,
+        log_max_buffer_count: 50,
+        line_end: '\n',
+        context_log_path: PathBuf::from("./logs/57"),
+        sink_failure_limit: 3,
+        ..Default::default()
+    };
+    let log_path = config.context_log_path.join("test_log.log");
+
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread.add_named_sink("broken", {
+        let calls = calls.clone();
+        move |_: &LogData| -> crate::Result<()> {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(color_eyre::eyre::eyre!("connection refused"))
+        }
+    });
+    logger_thread.add_named_sink("working", {
+        let received = received.clone();
+        move |log: &LogData| -> crate::Result<()> {
+            received.lock().unwrap().push(log.message.clone());
+            Ok(())
+        }
+    });
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+
+    let logger_thread = thread_safe_logger.read().unwrap();
+    for i in 0..5 {
+        logger_thread
+            .queue_log(LogData {
+                level: LogLevel::Info,
+                message: format!("log {i}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    // the warning and the notice of disabling it are queued by the writer, write them as well
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+
+    assert!(logger_thread.is_writer_alive());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert!(logger_thread.last_sink_error().unwrap().contains("connection refused"));
+    let received = received.lock().unwrap();
+    assert!((0..5).all(|i| received.contains(&format!("log {i}"))));
+
+    let file = std::fs::read_to_string(log_path).unwrap();
+    assert_eq!(file.matches("sink \"broken\" failed: connection refused").count(), 1);
+    assert!(file.contains("sink \"broken\" failed 3 times in a row and was disabled"));
+
+    logger_thread.shutdown().unwrap();
+}
@@ -1,6 +1,6 @@
-use std::{fs, time::Duration};
+use std::{fs, path::PathBuf, time::Duration};
 
-use crate::{LoggerConfig, LoggerThread};
+use crate::{logger::LogData, LogLevel, LoggerConfig, LoggerThread};
 
 #[test]
 fn test_logger_initialization() {
@@ -9,6 +9,7 @@ fn test_logger_initialization() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: "./logs/10".into(),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -30,6 +31,7 @@ fn test_logger_config() {
         log_max_buffer_count: 100,
         line_end: '\r',
         context_log_path: "./logs/1".into(),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -45,6 +47,7 @@ fn test_logger_thread_safe() {
         log_max_buffer_count: 50,
         line_end: '\n',
         context_log_path: "./logs/1".into(),
+        ..Default::default()
     };
     let log_path = config.context_log_path.join("test_log.log");
 
@@ -55,3 +58,229 @@ fn test_logger_thread_safe() {
 
     fs::remove_file(log_path).unwrap();
 }
+
+#[test]
+fn test_logger_config_builder() {
+    let config = LoggerConfig::builder()
+        .max_string_len(200)
+        .line_end('\r')
+        .flush_interval(Duration::from_millis(100))
+        .max_drain_batch(10)
+        .flush_on_level(Some(crate::LogLevel::Error))
+        .context_log_path("./logs/16")
+        .write_buffer_capacity(64 * 1024)
+        .flush_every_line(true)
+        .build()
+        .unwrap();
+
+    assert_eq!(config.max_string_len, 200);
+    assert_eq!(config.log_max_buffer_count, 50);
+    assert_eq!(config.line_end, '\r');
+    assert_eq!(config.flush_interval, Duration::from_millis(100));
+    assert_eq!(config.flush_log_count, 50);
+    assert_eq!(config.max_drain_batch, 10);
+    assert_eq!(config.flush_on_level, Some(crate::LogLevel::Error));
+    assert_eq!(config.context_log_path, PathBuf::from("./logs/16"));
+    assert_eq!(config.write_buffer_capacity, 64 * 1024);
+    assert!(config.flush_every_line);
+}
+
+#[test]
+fn test_logger_config_invalid_timestamp_format() {
+    let result = LoggerConfig::builder()
+        .timestamp_format("%Y-%m-%d %Q")
+        .use_local_time(true)
+        .build();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_logger_config_invalid_line_template() {
+    assert!(LoggerConfig::builder()
+        .line_template("{level} {message}")
+        .build()
+        .is_ok());
+    assert!(LoggerConfig::builder().line_template("{level} {msg}").build().is_err());
+    assert!(LoggerConfig::builder().line_template("{level").build().is_err());
+    assert!(LoggerConfig::builder().line_template("level}").build().is_err());
+}
+
+#[test]
+fn test_logger_config_min_level_from_env() {
+    use crate::LogLevel;
+
+    assert_eq!("warn".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+    assert_eq!("e".parse::<LogLevel>().unwrap(), LogLevel::Error);
+    assert!("verbose".parse::<LogLevel>().is_err());
+
+    let mut config = LoggerConfig::default();
+    config.min_level_from_env("PAPERLOG_TEST_LEVEL_UNSET").unwrap();
+    assert_eq!(config.min_level, LogLevel::Debug);
+
+    std::env::set_var("PAPERLOG_TEST_LEVEL", "Info");
+    config.min_level_from_env("PAPERLOG_TEST_LEVEL").unwrap();
+    assert_eq!(config.min_level, LogLevel::Info);
+
+    std::env::set_var("PAPERLOG_TEST_LEVEL", "loud");
+    assert!(config.min_level_from_env("PAPERLOG_TEST_LEVEL").is_err());
+    assert_eq!(config.min_level, LogLevel::Info);
+}
+
+#[test]
+fn test_logger_config_zero_max_string_len() {
+    let result = LoggerConfig::builder().max_string_len(0).build();
+    assert!(result.is_err());
+
+    let config = LoggerConfig {
+        max_string_len: 0,
+        context_log_path: "./logs/37".into(),
+        ..Default::default()
+    };
+    let log_path = PathBuf::from("./logs/37/test_log.log");
+    assert!(LoggerThread::new(config, log_path).is_err());
+}
+
+#[test]
+fn test_logger_append() {
+    let log_path = PathBuf::from("./logs/41/test_log.log");
+
+    let write_session = |append: bool, message: &str| {
+        let config = LoggerConfig {
+            context_log_path: "./logs/41".into(),
+            append,
+            ..Default::default()
+        };
+        let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+        logger_thread
+            .log_sync(LogData {
+                message: message.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    };
+
+    write_session(false, "first session");
+    write_session(true, "second session");
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert!(log.contains("first session"));
+    assert!(log.contains("second session"));
+
+    write_session(false, "third session");
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    assert!(!log.contains("first session"));
+    assert!(log.contains("third session"));
+}
+
+#[test]
+fn test_logger_startup_banner() {
+    let config = LoggerConfig {
+        context_log_path: "./logs/45".into(),
+        log_startup_banner: true,
+        ..Default::default()
+    };
+    let log_path = PathBuf::from("./logs/45/test_log.log");
+
+    let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+    logger_thread
+        .queue_log(LogData {
+            message: "queued before init".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    thread_safe_logger.read().unwrap().shutdown().unwrap();
+
+    let log = fs::read_to_string(&log_path).unwrap();
+    let mut lines = log.lines();
+    let banner = lines.next().unwrap();
+    assert!(banner.contains(concat!("paper2 ", env!("CARGO_PKG_VERSION"), " started")));
+    assert!(banner.contains(" min_level=DEBUG"));
+    assert!(banner.contains(" log_path=./logs/45/test_log.log"));
+    assert!(lines.next().unwrap().contains("queued before init"));
+}
+
+#[test]
+fn test_logger_log_dir_is_file() {
+    let config = LoggerConfig {
+        context_log_path: "./logs/26/not_a_dir".into(),
+        ..Default::default()
+    };
+    fs::create_dir_all("./logs/26").unwrap();
+    fs::write(&config.context_log_path, "").unwrap();
+    let log_path = PathBuf::from("./logs/26/test_log.log");
+
+    let error = LoggerThread::new(config, log_path).err().unwrap();
+    assert!(error.to_string().contains("is not a directory"));
+}
+
+#[test]
+fn test_logger_file_error_fallback() {
+    let config = LoggerConfig {
+        context_log_path: "./logs/46/not_a_dir".into(),
+        fallback_to_stdout_on_file_error: true,
+        ..Default::default()
+    };
+    fs::create_dir_all("./logs/46").unwrap();
+    fs::write(&config.context_log_path, "").unwrap();
+    let log_path = PathBuf::from("./logs/46/not_a_dir/test_log.log");
+
+    let mut logger_thread = LoggerThread::new(config, log_path).unwrap();
+    logger_thread.add_context("Ignored").unwrap();
+    assert!(logger_thread.contexts().is_empty());
+
+    let thread_safe_logger = logger_thread.init(false).unwrap();
+    let logger_thread = thread_safe_logger.read().unwrap();
+    logger_thread
+        .queue_log(LogData {
+            message: "stdout only".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(logger_thread.flush(Some(Duration::from_secs(5))).unwrap());
+    logger_thread.shutdown().unwrap();
+}
+
+#[test]
+fn test_independent_loggers() {
+    let new_logger = |dir: &str, min_level: LogLevel| {
+        let config = LoggerConfig {
+            context_log_path: format!("./logs/{dir}").into(),
+            min_level,
+            ..Default::default()
+        };
+        let log_path = config.context_log_path.join("test_log.log");
+        let logger_thread = LoggerThread::new(config, log_path.clone()).unwrap();
+        (logger_thread.init(false).unwrap(), log_path)
+    };
+    let (network, network_path) = new_logger("65", LogLevel::Debug);
+    let (storage, storage_path) = new_logger("66", LogLevel::Warn);
+
+    for (logger, name) in [(&network, "network"), (&storage, "storage")] {
+        for level in [LogLevel::Debug, LogLevel::Error] {
+            logger
+                .read()
+                .unwrap()
+                .queue_log(LogData {
+                    level,
+                    message: format!("{name} {level:?}"),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+    }
+    // shutting one down leaves the other running
+    storage.read().unwrap().shutdown().unwrap();
+    let network = network.read().unwrap();
+    assert!(network.is_writer_alive());
+    assert!(network.flush(Some(Duration::from_secs(5))).unwrap());
+
+    let network_log = fs::read_to_string(network_path).unwrap();
+    assert!(network_log.contains("network Debug") && network_log.contains("network Error"));
+    assert!(!network_log.contains("storage"));
+    let storage_log = fs::read_to_string(storage_path).unwrap();
+    assert!(!storage_log.contains("storage Debug") && storage_log.contains("storage Error"));
+    assert!(!storage_log.contains("network"));
+
+    network.shutdown().unwrap();
+}